@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use bon::Builder;
+use spacetimedb::{ReducerContext, SpacetimeType, Table, table};
+
+use crate::{
+    behavior::BehaviorTreeId,
+    collisions::{Collider, ColliderType, RigidBody, RigidBodyType},
+    math::Vec3,
+    navigation::NavigationAgent,
+    utils::{Entity, WorldEntity},
+    world::WorldId,
+};
+
+pub type ArchetypeId = u32;
+
+/// The shape of the collider created for an archetype. Fields are interpreted
+/// the same way as in [`Collider`].
+#[derive(SpacetimeType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ArchetypeCollider {
+    pub collider_type: ColliderType,
+    pub radius: f32,
+    pub normal: Vec3,
+    pub height: f32,
+    pub size: Vec3,
+    pub point_a: Vec3,
+    pub point_b: Vec3,
+    pub point_c: Vec3,
+}
+
+impl ArchetypeCollider {
+    /// Builds the collider of the archetype for the given world.
+    pub fn to_collider(&self, world_id: WorldId) -> Collider {
+        Collider {
+            id: 0,
+            world_id,
+            radius: self.radius,
+            normal: self.normal,
+            height: self.height,
+            size: self.size,
+            point_a: self.point_a,
+            point_b: self.point_b,
+            point_c: self.point_c,
+            collider_type: self.collider_type,
+        }
+    }
+}
+
+/// The navigation agent settings of an archetype.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub struct ArchetypeAgent {
+    /// The radius of the agent.
+    pub radius: f32,
+    /// The speed the agent prefers to move at.
+    pub desired_speed: f32,
+    /// The maximum speed that the agent can move at.
+    pub max_speed: f32,
+}
+
+#[table(accessor = steng_archetypes)]
+#[derive(Builder, Clone, Debug)]
+/// A prefab describing the engine entities to create when spawning something in
+/// a world, e.g. a summoned creature.
+pub struct Archetype {
+    #[primary_key]
+    #[auto_inc]
+    #[builder(default = 0)]
+    /// The unique ID of the archetype.
+    pub id: ArchetypeId,
+    pub name: String,
+    /// The collider of the spawned rigid body.
+    pub collider: ArchetypeCollider,
+    /// The type of the spawned rigid body.
+    #[builder(default = RigidBodyType::default())]
+    pub body_type: RigidBodyType,
+    /// The navigation agent settings, if the archetype can move on navigation meshes.
+    pub agent: Option<ArchetypeAgent>,
+    /// The behavior tree driving the archetype, if any.
+    pub behavior_tree_id: Option<BehaviorTreeId>,
+}
+
+/// The engine entities created when spawning an archetype.
+pub struct SpawnedArchetype {
+    pub archetype_id: ArchetypeId,
+    pub collider: Collider,
+    pub rigid_body: RigidBody,
+    pub agent: Option<NavigationAgent>,
+    pub behavior_tree_id: Option<BehaviorTreeId>,
+}
+
+impl Entity for Archetype {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_archetypes().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_archetypes().id().find(id as ArchetypeId)
+    }
+
+    fn iter(ctx: &ReducerContext) -> impl Iterator<Item = Self> {
+        ctx.db.steng_archetypes().iter()
+    }
+
+    fn as_map(ctx: &ReducerContext) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_archetypes()
+            .iter()
+            .map(|archetype| (archetype.id as u64, archetype))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext) -> Vec<Self> {
+        ctx.db.steng_archetypes().iter().collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_archetypes().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_archetypes().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext) {
+        ctx.db.steng_archetypes().iter().for_each(|archetype| {
+            ctx.db.steng_archetypes().id().delete(archetype.id);
+        });
+    }
+
+    fn count(ctx: &ReducerContext) -> u64 {
+        ctx.db.steng_archetypes().count()
+    }
+}
+
+/// Spawns an archetype at the given position, creating its collider, rigid body
+/// and, if the archetype has agent settings, its navigation agent.
+///
+/// The rigid body and agent are linked to the entity `external_id`, so that the
+/// spawned entity can be targeted by spells. Returns `None` if the archetype does
+/// not exist.
+pub fn spawn_archetype(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    archetype_id: ArchetypeId,
+    position: Vec3,
+    external_id: u64,
+) -> Option<SpawnedArchetype> {
+    let archetype = Archetype::find(ctx, archetype_id as u64)?;
+
+    let collider = archetype.collider.to_collider(world_id).insert(ctx);
+    let rigid_body = RigidBody::builder()
+        .world_id(world_id)
+        .position(position)
+        .body_type(archetype.body_type)
+        .collider_id(collider.id)
+        .external_id(external_id)
+        .build()
+        .insert(ctx);
+    let agent = archetype.agent.map(|settings| {
+        NavigationAgent::builder()
+            .world_id(world_id)
+            .external_id(external_id)
+            .rigid_body_id(rigid_body.id)
+            .position(position)
+            .radius(settings.radius)
+            .desired_speed(settings.desired_speed)
+            .max_speed(settings.max_speed)
+            .build()
+            .insert(ctx)
+    });
+
+    Some(SpawnedArchetype {
+        archetype_id,
+        collider,
+        rigid_body,
+        agent,
+        behavior_tree_id: archetype.behavior_tree_id,
+    })
+}
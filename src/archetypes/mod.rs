@@ -0,0 +1,3 @@
+mod archetype;
+
+pub use archetype::*;
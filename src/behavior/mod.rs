@@ -3,4 +3,4 @@ mod behavior_state;
 
 pub use ai_behavior::{Action, AlwaysSucceed, Fail, If, Select, Sequence, Status};
 pub use behavior_impl::{BehaviorExecutor, tick_behavior};
-pub use behavior_state::BehaviorTree;
+pub use behavior_state::{BehaviorTree, BehaviorTreeId};
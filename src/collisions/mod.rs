@@ -1,11 +1,13 @@
 mod colliders;
+mod query;
 mod ray_cast;
 mod rigid_body;
-mod shape_wrapper;
-mod tick;
+pub(crate) mod shape_wrapper;
+pub(crate) mod tick;
 mod triggers;
 
 pub use colliders::{Collider, ColliderId, ColliderType};
+pub use query::{CollisionQuery, query_overlaps, query_sweep};
 pub use ray_cast::{RayCast, RayCastBuilder, RayCastHit, RayCastId};
 pub use rigid_body::{RigidBody, RigidBodyId, RigidBodyType};
 pub use tick::tick_collisions;
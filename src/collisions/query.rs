@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use parry3d::{bounding_volume::BoundingVolume, math::Pose3, partitioning::Bvh};
+use spacetimedb::ReducerContext;
+
+use crate::{
+    collisions::{
+        Collider, ColliderId, RayCastHit, RigidBody, RigidBodyId, shape_wrapper::ShapeWrapper,
+        tick::build_bvh,
+    },
+    math::{Quat, Vec3},
+    utils::WorldEntity,
+    world::World,
+};
+
+/// A snapshot of the rigid bodies of a world, used to run one-shot queries
+/// without inserting triggers or raycasts. Building it costs as much as a
+/// collisions broad phase, so reuse it when running several queries in a row.
+pub struct CollisionQuery {
+    colliders: HashMap<ColliderId, ShapeWrapper>,
+    rigid_bodies: Vec<RigidBody>,
+    body_ids: Vec<RigidBodyId>,
+    bvh: Bvh,
+    aabb_dilation_factor: f32,
+}
+
+impl CollisionQuery {
+    pub fn new(ctx: &ReducerContext, world: &World) -> Self {
+        let colliders: HashMap<ColliderId, ShapeWrapper> = Collider::iter(ctx, world.id)
+            .map(|collider| {
+                let shape = ShapeWrapper::from(&collider);
+                (collider.id, shape)
+            })
+            .collect();
+        let rigid_bodies = RigidBody::as_vec(ctx, world.id);
+        let (bvh, body_ids) = build_bvh(&rigid_bodies, &colliders, world);
+
+        Self {
+            colliders,
+            rigid_bodies,
+            body_ids,
+            bvh,
+            aabb_dilation_factor: world.aabb_dilation_factor,
+        }
+    }
+
+    /// Returns the rigid bodies overlapping the given collider placed at `position`
+    /// and `rotation`. The collider is only used as a shape and does not need to be
+    /// inserted in the world.
+    pub fn overlaps(
+        &self,
+        collider: &Collider,
+        position: Vec3,
+        rotation: Quat,
+    ) -> Vec<RigidBodyId> {
+        let shape = ShapeWrapper::from(collider);
+        let isometry = Pose3::from_parts(position.into(), rotation.into());
+        let aabb = shape.collision_aabb(&isometry, self.aabb_dilation_factor);
+
+        self.bvh
+            .intersect_aabb(&aabb)
+            .filter_map(|leaf_idx| {
+                let idx = leaf_idx as usize;
+                let rigid_body = &self.rigid_bodies[idx];
+                let rigid_body_collider = self.colliders.get(&rigid_body.collider_id).unwrap();
+                shape
+                    .intersects(&isometry, &rigid_body.into(), rigid_body_collider)
+                    .then_some(self.body_ids[idx])
+            })
+            .collect()
+    }
+
+    /// Sweeps the given collider from `position` along `motion` and returns the first
+    /// rigid body it touches, ignoring the bodies in `ignored`. The hit distance is
+    /// measured along `motion`, and the hit position is the contact point on the body.
+    pub fn sweep(
+        &self,
+        collider: &Collider,
+        position: Vec3,
+        rotation: Quat,
+        motion: Vec3,
+        ignored: &[RigidBodyId],
+    ) -> Option<RayCastHit> {
+        let shape = ShapeWrapper::from(collider);
+        let start = Pose3::from_parts(position.into(), rotation.into());
+        let end = Pose3::from_parts((position + motion).into(), rotation.into());
+        let aabb = shape
+            .collision_aabb(&start, self.aabb_dilation_factor)
+            .merged(&shape.collision_aabb(&end, self.aabb_dilation_factor));
+        let length = motion.length();
+
+        self.bvh
+            .intersect_aabb(&aabb)
+            .filter_map(|leaf_idx| {
+                let idx = leaf_idx as usize;
+                let rigid_body_id = self.body_ids[idx];
+                if ignored.contains(&rigid_body_id) {
+                    return None;
+                }
+
+                let rigid_body = &self.rigid_bodies[idx];
+                let rigid_body_collider = self.colliders.get(&rigid_body.collider_id).unwrap();
+                let rigid_body_isometry: Pose3 = rigid_body.into();
+                let hit = shape.cast_shape(
+                    &start,
+                    motion.into(),
+                    &rigid_body_isometry,
+                    rigid_body_collider,
+                )?;
+
+                Some(RayCastHit {
+                    distance: hit.time_of_impact * length,
+                    position: rigid_body_isometry.transform_point(hit.witness2).into(),
+                    normal: (rigid_body_isometry.rotation * hit.normal2).into(),
+                    rigid_body_id,
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+/// Returns the rigid bodies of the world overlapping the given collider placed at
+/// `position` and `rotation`, making this suitable for one-shot queries such as
+/// area of effect spells. See [`CollisionQuery::overlaps`].
+pub fn query_overlaps(
+    ctx: &ReducerContext,
+    world: &World,
+    collider: &Collider,
+    position: Vec3,
+    rotation: Quat,
+) -> Vec<RigidBodyId> {
+    CollisionQuery::new(ctx, world).overlaps(collider, position, rotation)
+}
+
+/// Returns the first rigid body of the world touched by the given collider moving
+/// from `position` along `motion`. See [`CollisionQuery::sweep`].
+pub fn query_sweep(
+    ctx: &ReducerContext,
+    world: &World,
+    collider: &Collider,
+    position: Vec3,
+    rotation: Quat,
+    motion: Vec3,
+    ignored: &[RigidBodyId],
+) -> Option<RayCastHit> {
+    CollisionQuery::new(ctx, world).sweep(collider, position, rotation, motion, ignored)
+}
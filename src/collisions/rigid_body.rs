@@ -30,6 +30,9 @@ pub struct RigidBody {
     #[index(btree)]
     #[builder(default = 1)]
     pub world_id: u64,
+    /// An optional external ID for the body. This can be used to link the
+    /// body to an entity in another system.
+    pub external_id: Option<u64>,
     #[builder(default = Vec3::ZERO)]
     pub position: Vec3,
     #[builder(default = Quat::IDENTITY)]
@@ -39,6 +42,16 @@ pub struct RigidBody {
     pub body_type: RigidBodyType,
 
     pub collider_id: u64,
+
+    /// If true, navigation agents avoid this body like a character, using the
+    /// horizontal footprint of its collider. Meant for dynamic objects such as
+    /// crates, static geometry belongs in the navigation mesh. Bodies bound to an
+    /// agent are ignored, the agent already being avoided.
+    ///
+    /// The footprint is approximated by its bounding circle, so long thin bodies
+    /// (e.g. doors) block a much larger area than they cover.
+    #[builder(default = false)]
+    pub navigation_obstacle: bool,
 }
 
 impl WorldEntity for RigidBody {
@@ -1,7 +1,10 @@
 use parry3d::{
     bounding_volume::{Aabb, BoundingVolume},
     math::{Pose3, Vector},
-    query::{Ray, RayCast, RayIntersection, intersection_test},
+    query::{
+        Ray, RayCast, RayIntersection, ShapeCastHit, ShapeCastOptions, cast_shapes,
+        intersection_test,
+    },
     shape::{Ball, Capsule, Cone, Cuboid, Cylinder, HalfSpace, Shape, Triangle},
 };
 
@@ -78,12 +81,7 @@ impl ShapeWrapper {
         }
     }
 
-    pub fn intersects(
-        &self,
-        isometry_a: &Pose3,
-        isometry_b: &Pose3,
-        other: &ShapeWrapper,
-    ) -> bool {
+    pub fn intersects(&self, isometry_a: &Pose3, isometry_b: &Pose3, other: &ShapeWrapper) -> bool {
         let result = intersection_test(
             isometry_a,
             self.as_parry_shape(),
@@ -93,6 +91,28 @@ impl ShapeWrapper {
 
         result.unwrap_or_default()
     }
+
+    /// Moves this shape along `motion` and returns the first contact with the other,
+    /// static, shape. The time of impact is expressed as a fraction of `motion`.
+    pub fn cast_shape(
+        &self,
+        isometry: &Pose3,
+        motion: Vector,
+        other_isometry: &Pose3,
+        other: &ShapeWrapper,
+    ) -> Option<ShapeCastHit> {
+        let result = cast_shapes(
+            isometry,
+            motion,
+            self.as_parry_shape(),
+            other_isometry,
+            Vector::ZERO,
+            other.as_parry_shape(),
+            ShapeCastOptions::with_max_time_of_impact(1.0),
+        );
+
+        result.ok().flatten()
+    }
 }
 
 impl From<Collider> for ShapeWrapper {
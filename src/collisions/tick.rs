@@ -71,23 +71,7 @@ fn run_broad_phase(
     HashMap<RayCastId, Vec<RigidBodyId>>,
     HashMap<TriggerId, Vec<RigidBodyId>>,
 ) {
-    let mut aabbs = Vec::with_capacity(rigid_bodies.len());
-    let mut body_ids = Vec::with_capacity(rigid_bodies.len());
-
-    for rb in rigid_bodies {
-        let collider = colliders.get(&rb.collider_id).unwrap();
-        let position = rb.position.into();
-        let rotation = rb.rotation.into();
-        let aabb = collider.collision_aabb(
-            &Pose3::from_parts(position, rotation),
-            world.aabb_dilation_factor,
-        );
-
-        aabbs.push(aabb);
-        body_ids.push(rb.id);
-    }
-
-    let bvh = Bvh::from_leaves(BvhBuildStrategy::Binned, &aabbs);
+    let (bvh, body_ids) = build_bvh(rigid_bodies, colliders, world);
 
     let mut raycast_hits: HashMap<RayCastId, Vec<RigidBodyId>> = HashMap::new();
     for raycast in raycasts.values() {
@@ -129,6 +113,32 @@ fn run_broad_phase(
     (raycast_hits, trigger_hits)
 }
 
+/// Builds a BVH over the AABBs of the rigid bodies. Leaf indices map to the
+/// returned rigid body IDs.
+pub(crate) fn build_bvh(
+    rigid_bodies: &[RigidBody],
+    colliders: &HashMap<ColliderId, ShapeWrapper>,
+    world: &World,
+) -> (Bvh, Vec<RigidBodyId>) {
+    let mut aabbs = Vec::with_capacity(rigid_bodies.len());
+    let mut body_ids = Vec::with_capacity(rigid_bodies.len());
+
+    for rb in rigid_bodies {
+        let collider = colliders.get(&rb.collider_id).unwrap();
+        let position = rb.position.into();
+        let rotation = rb.rotation.into();
+        let aabb = collider.collision_aabb(
+            &Pose3::from_parts(position, rotation),
+            world.aabb_dilation_factor,
+        );
+
+        aabbs.push(aabb);
+        body_ids.push(rb.id);
+    }
+
+    (Bvh::from_leaves(BvhBuildStrategy::Binned, &aabbs), body_ids)
+}
+
 fn run_narrow_phase(
     broad_raycast_hits: HashMap<RayCastId, Vec<RigidBodyId>>,
     broad_trigger_hits: HashMap<TriggerId, Vec<RigidBodyId>>,
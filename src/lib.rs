@@ -1,3 +1,4 @@
+pub mod archetypes;
 pub mod behavior;
 pub mod collisions;
 pub mod math;
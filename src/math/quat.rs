@@ -2,6 +2,8 @@ use parry3d::math::Rot3;
 use serde::{Deserialize, Serialize};
 use spacetimedb::SpacetimeType;
 
+use crate::math::Vec3;
+
 #[derive(SpacetimeType, Clone, Debug, Copy, PartialEq, Default, Deserialize, Serialize)]
 pub struct Quat {
     pub x: f32,
@@ -17,6 +19,26 @@ impl Quat {
         z: 0.0,
         w: 1.0,
     };
+
+    /// Creates a rotation of `angle` radians around the Y (up) axis.
+    pub fn from_rotation_y(angle: f32) -> Self {
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Self {
+            x: 0.0,
+            y: sin,
+            z: 0.0,
+            w: cos,
+        }
+    }
+
+    /// Creates a rotation around the Y (up) axis making Z forward face `direction`.
+    /// The vertical component of `direction` is ignored.
+    pub fn facing(direction: Vec3) -> Self {
+        if direction.x == 0.0 && direction.z == 0.0 {
+            return Self::IDENTITY;
+        }
+        Self::from_rotation_y(direction.x.atan2(direction.z))
+    }
 }
 
 impl From<Rot3> for Quat {
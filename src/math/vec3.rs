@@ -78,6 +78,11 @@ impl Vec3 {
         let dz = self.z - other.z;
         dx * dx + dy * dy + dz * dz
     }
+
+    /// Linearly interpolates between `self` (t = 0) and `other` (t = 1).
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
 }
 
 impl Display for Vec3 {
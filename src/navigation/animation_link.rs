@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use bon::Builder;
+use landmass::AnimationLink;
+use spacetimedb::{ReducerContext, SpacetimeType, Table, table};
+
+use crate::{math::Vec3, navigation::coordinates::XYZ, utils::WorldEntity, world::WorldId};
+
+pub type NavAnimationLinkId = u64;
+
+#[table(accessor = steng_navigation_animation_links)]
+#[derive(Builder, Clone, Debug, PartialEq)]
+/// An off-mesh connection between two edges of the navigation meshes of a world,
+/// e.g. a jump, a ladder or a teleporter. Agents path through links like through
+/// regular polygons, then stop in the
+/// [`crate::navigation::NavigationState::ReachedAnimationLink`] state and wait
+/// for the game to play the traversal.
+pub struct NavAnimationLink {
+    #[primary_key]
+    #[auto_inc]
+    #[builder(default = 0)]
+    pub id: NavAnimationLinkId,
+    #[index(btree)]
+    #[builder(default = 1)]
+    pub world_id: WorldId,
+    /// The first point of the edge the link can be entered from.
+    pub start_edge_a: Vec3,
+    /// The second point of the edge the link can be entered from.
+    pub start_edge_b: Vec3,
+    /// The first point of the edge the link leads to.
+    pub end_edge_a: Vec3,
+    /// The second point of the edge the link leads to.
+    pub end_edge_b: Vec3,
+    /// A game-defined kind, used to pick the traversal to play.
+    #[builder(default = 0)]
+    pub kind: u32,
+    /// The cost of using the link during pathfinding.
+    #[builder(default = 1.0)]
+    pub cost: f32,
+    /// If true, the link can also be used from the end edge to the start edge.
+    #[builder(default = false)]
+    pub bidirectional: bool,
+}
+
+/// The animation link an agent reached along its path.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub struct ReachedAnimationLink {
+    /// The link that was reached.
+    pub link_id: NavAnimationLinkId,
+    /// The point where the agent enters the link.
+    pub start_point: Vec3,
+    /// The point where the agent leaves the link.
+    pub end_point: Vec3,
+}
+
+/// An animation link action requested by the game, applied on the next
+/// navigation tick.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub enum AnimationLinkRequest {
+    /// Start using the reached animation link.
+    Start,
+    /// The traversal of the animation link is over.
+    Finish,
+}
+
+impl WorldEntity for NavAnimationLink {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_animation_links().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_animation_links().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_navigation_animation_links()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_navigation_animation_links()
+            .world_id()
+            .filter(world_id)
+            .map(|link| (link.id, link))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_navigation_animation_links()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_animation_links().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db
+            .steng_navigation_animation_links()
+            .id()
+            .delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_navigation_animation_links()
+            .world_id()
+            .filter(world_id)
+            .for_each(|link| {
+                ctx.db
+                    .steng_navigation_animation_links()
+                    .id()
+                    .delete(link.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_navigation_animation_links()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl From<&NavAnimationLink> for AnimationLink<XYZ> {
+    fn from(value: &NavAnimationLink) -> Self {
+        AnimationLink {
+            start_edge: (value.start_edge_a, value.start_edge_b),
+            end_edge: (value.end_edge_a, value.end_edge_b),
+            kind: value.kind as usize,
+            cost: value.cost,
+            bidirectional: value.bidirectional,
+        }
+    }
+}
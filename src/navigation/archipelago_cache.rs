@@ -0,0 +1,341 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use landmass::{
+    AgentId, AnimationLinkId, Character as LmCharacter, CharacterId, Island, IslandId, Transform,
+};
+use spacetimedb::ReducerContext;
+
+use crate::{
+    math::Vec3,
+    navigation::{
+        Archipelago, Character, NavAnimationLink, NavAnimationLinkId, NavNodeType, NavNodeTypeId,
+        NavigationAgent, NavigationAgentId, NavigationSettings, navigation_path::PathSource,
+        validated_navmesh::NavMesh,
+    },
+    utils::WorldEntity,
+    world::WorldId,
+};
+
+thread_local! {
+    /// The archipelagos of the worlds, kept in module memory between ticks so that
+    /// agents keep their paths and navigation meshes are only decoded when they change.
+    static ARCHIPELAGOS: RefCell<HashMap<WorldId, CachedArchipelago>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The archipelago of a world along with the mapping between the engine rows and
+/// the landmass objects they were added as.
+///
+/// The cache lives outside of the database and is not rolled back with a failed
+/// transaction. It is therefore only a view of the tables: navigation meshes are
+/// matched by ID and revision, agents are synced from their rows every tick.
+pub(crate) struct CachedArchipelago {
+    pub(crate) archipelago: Archipelago,
+    /// Navigation mesh ID to its island and the row it was built from.
+    islands: HashMap<u64, (IslandId, IslandSource)>,
+    /// Island to the ID of the navigation mesh it was built from.
+    island_navmeshes: HashMap<IslandId, u64>,
+    /// Navigation mesh ID to the row that failed to decode, so that it is not
+    /// decoded and reported again every tick.
+    corrupted_navmeshes: HashMap<u64, IslandSource>,
+    agents: HashMap<NavigationAgentId, AgentId>,
+    characters: Vec<CharacterId>,
+    /// Animation link ID to its landmass ID and the row it was built from.
+    animation_links: HashMap<NavAnimationLinkId, (AnimationLinkId, NavAnimationLink)>,
+    /// Landmass animation link ID to the engine ID of the link.
+    animation_link_ids: HashMap<AnimationLinkId, NavAnimationLinkId>,
+    /// The type indices whose cost was set from a node type, with their cost.
+    type_indices: HashMap<usize, f32>,
+    /// Node type ID to its invalid cost, so that it is not reported again every
+    /// tick until the cost changes.
+    invalid_node_types: HashMap<NavNodeTypeId, f32>,
+    /// Incremented whenever islands, animation links or node type costs change,
+    /// so that the exposed paths computed before are computed again.
+    pub(crate) generation: u64,
+    /// What the exposed path of each agent was last computed from.
+    pub(crate) path_sources: HashMap<NavigationAgentId, PathSource>,
+}
+
+/// What an island was built from. Rows are compared by revision, as bumped by
+/// [`NavMesh::update`], but also by data length and transform so that most rows
+/// updated through the table directly are reloaded too.
+#[derive(Clone, Copy, PartialEq)]
+struct IslandSource {
+    revision: u64,
+    data_len: usize,
+    translation: Vec3,
+    rotation: f32,
+}
+
+impl From<&NavMesh> for IslandSource {
+    fn from(navmesh: &NavMesh) -> Self {
+        Self {
+            revision: navmesh.revision,
+            data_len: navmesh.data.len(),
+            translation: navmesh.translation,
+            rotation: navmesh.rotation,
+        }
+    }
+}
+
+impl CachedArchipelago {
+    fn new(settings: &NavigationSettings) -> Self {
+        Self {
+            archipelago: Archipelago::new(settings.into()),
+            islands: HashMap::new(),
+            island_navmeshes: HashMap::new(),
+            corrupted_navmeshes: HashMap::new(),
+            agents: HashMap::new(),
+            characters: Vec::new(),
+            animation_links: HashMap::new(),
+            animation_link_ids: HashMap::new(),
+            type_indices: HashMap::new(),
+            invalid_node_types: HashMap::new(),
+            generation: 0,
+            path_sources: HashMap::new(),
+        }
+    }
+
+    /// Adds the navigation meshes that were inserted or updated since the last sync
+    /// and removes the islands of deleted ones. Navigation meshes that cannot be
+    /// decoded are skipped and logged.
+    pub(crate) fn sync_islands(&mut self, ctx: &ReducerContext, world_id: WorldId) {
+        let mut seen = HashSet::new();
+        for navmesh in NavMesh::iter(ctx, world_id) {
+            seen.insert(navmesh.id);
+            let source = IslandSource::from(&navmesh);
+            match self.islands.get(&navmesh.id) {
+                Some((_, cached)) if *cached == source => continue,
+                Some((island_id, _)) => {
+                    self.archipelago.remove_island(*island_id);
+                    self.island_navmeshes.remove(island_id);
+                    self.islands.remove(&navmesh.id);
+                    self.generation += 1;
+                }
+                None => {}
+            }
+            if self.corrupted_navmeshes.get(&navmesh.id) == Some(&source) {
+                continue;
+            }
+
+            let nav_mesh = match navmesh.decode() {
+                Ok(nav_mesh) => nav_mesh,
+                Err(err) => {
+                    log::error!(
+                        "[World#{}] [Navigation] Skipping NavMesh#{}: {}",
+                        world_id,
+                        navmesh.id,
+                        err
+                    );
+                    self.corrupted_navmeshes.insert(navmesh.id, source);
+                    continue;
+                }
+            };
+            self.corrupted_navmeshes.remove(&navmesh.id);
+            let island_id = self.archipelago.add_island(Island::new(
+                Transform {
+                    translation: navmesh.translation,
+                    rotation: navmesh.rotation,
+                },
+                Arc::new(nav_mesh),
+            ));
+            self.islands.insert(navmesh.id, (island_id, source));
+            self.generation += 1;
+            self.island_navmeshes.insert(island_id, navmesh.id);
+        }
+
+        self.corrupted_navmeshes
+            .retain(|navmesh_id, _| seen.contains(navmesh_id));
+        self.islands.retain(|navmesh_id, (island_id, _)| {
+            let keep = seen.contains(navmesh_id);
+            if !keep {
+                self.archipelago.remove_island(*island_id);
+                self.island_navmeshes.remove(island_id);
+                self.generation += 1;
+            }
+            keep
+        });
+    }
+
+    /// Adds the animation links that were inserted or updated since the last sync
+    /// and removes the deleted ones.
+    pub(crate) fn sync_animation_links(&mut self, ctx: &ReducerContext, world_id: WorldId) {
+        let mut seen = HashSet::new();
+        for link in NavAnimationLink::iter(ctx, world_id) {
+            seen.insert(link.id);
+            match self.animation_links.get(&link.id) {
+                Some((_, cached)) if *cached == link => continue,
+                Some((link_id, _)) => {
+                    self.archipelago.remove_animation_link(*link_id);
+                    self.animation_link_ids.remove(link_id);
+                }
+                None => {}
+            }
+
+            let link_id = self.archipelago.add_animation_link((&link).into());
+            self.animation_link_ids.insert(link_id, link.id);
+            self.generation += 1;
+            self.animation_links.insert(link.id, (link_id, link));
+        }
+
+        self.animation_links.retain(|id, (link_id, _)| {
+            let keep = seen.contains(id);
+            if !keep {
+                self.archipelago.remove_animation_link(*link_id);
+                self.animation_link_ids.remove(link_id);
+                self.generation += 1;
+            }
+            keep
+        });
+    }
+
+    /// Returns the ID of the navigation mesh a landmass island was built from.
+    pub(crate) fn navmesh_id(&self, island_id: IslandId) -> Option<u64> {
+        self.island_navmeshes.get(&island_id).copied()
+    }
+
+    /// Returns the engine ID of a landmass animation link.
+    pub(crate) fn animation_link_id(&self, link_id: AnimationLinkId) -> Option<NavAnimationLinkId> {
+        self.animation_link_ids.get(&link_id).copied()
+    }
+
+    /// Sets the cost of the type indices of the world's node types. Type indices
+    /// whose node type was deleted go back to the default cost of 1.0. Invalid
+    /// costs are reported once, until they change.
+    pub(crate) fn sync_node_types(&mut self, ctx: &ReducerContext, world_id: WorldId) {
+        let mut type_indices = HashMap::new();
+        let mut invalid_node_types = HashMap::new();
+        for node_type in NavNodeType::iter(ctx, world_id) {
+            let type_index = node_type.type_index as usize;
+            let unchanged = self.type_indices.get(&type_index) == Some(&node_type.cost);
+            if !unchanged
+                && self
+                    .archipelago
+                    .set_type_index_cost(type_index, node_type.cost)
+                    .is_err()
+            {
+                let reported = self
+                    .invalid_node_types
+                    .get(&node_type.id)
+                    .is_some_and(|cost| cost.to_bits() == node_type.cost.to_bits());
+                if !reported {
+                    log::error!(
+                        "[World#{}] [Navigation] Invalid cost {} for NavNodeType#{} ({})",
+                        world_id,
+                        node_type.cost,
+                        node_type.id,
+                        node_type.name
+                    );
+                }
+                invalid_node_types.insert(node_type.id, node_type.cost);
+                continue;
+            }
+            type_indices.insert(type_index, node_type.cost);
+        }
+        self.invalid_node_types = invalid_node_types;
+
+        for type_index in self.type_indices.keys() {
+            if !type_indices.contains_key(type_index) {
+                let _ = self.archipelago.set_type_index_cost(*type_index, 1.0);
+            }
+        }
+        if self.type_indices != type_indices {
+            self.generation += 1;
+        }
+        self.type_indices = type_indices;
+    }
+
+    /// Adds new agents, updates existing ones from their rows (keeping their paths),
+    /// applies their animation link requests and removes the agents whose rows
+    /// were deleted. Returns the agents keyed by their landmass ID.
+    pub(crate) fn sync_agents(
+        &mut self,
+        agents: impl Iterator<Item = NavigationAgent>,
+    ) -> HashMap<AgentId, NavigationAgent> {
+        let mut synced = HashMap::new();
+        for mut eng_agent in agents {
+            let lm_agent = self
+                .agents
+                .get(&eng_agent.id())
+                .and_then(|agent_id| Some((*agent_id, self.archipelago.get_agent_mut(*agent_id)?)));
+
+            let agent_id = match lm_agent {
+                Some((agent_id, lm_agent)) => {
+                    eng_agent.sync_lm_agent(lm_agent);
+                    eng_agent.apply_animation_link_request(lm_agent);
+                    agent_id
+                }
+                None => {
+                    let agent_id = self.archipelago.add_agent((&eng_agent).into());
+                    self.agents.insert(eng_agent.id(), agent_id);
+                    agent_id
+                }
+            };
+            synced.insert(agent_id, eng_agent);
+        }
+
+        self.agents.retain(|_, agent_id| {
+            let keep = synced.contains_key(agent_id);
+            if !keep {
+                self.archipelago.remove_agent(*agent_id);
+            }
+            keep
+        });
+
+        synced
+    }
+
+    /// Replaces the characters of the archipelago. Characters are not persisted
+    /// by the engine and are provided again every tick.
+    pub(crate) fn sync_characters(&mut self, characters: impl Iterator<Item = Character>) {
+        for character_id in self.characters.drain(..) {
+            self.archipelago.remove_character(character_id);
+        }
+
+        for character in characters {
+            let character_id = self.archipelago.add_character(LmCharacter {
+                position: character.position,
+                velocity: character.velocity,
+                radius: character.radius,
+            });
+            self.characters.push(character_id);
+        }
+    }
+}
+
+/// Runs `f` with the cached archipelago of the world, creating it if needed.
+/// The archipelago options are refreshed from the world's settings.
+///
+/// The archipelago is taken out of the cache while `f` runs, so that queries
+/// nested in `f` (e.g. [`crate::navigation::find_path`] called from a callback
+/// during the navigation tick) do not conflict with it: they build a temporary
+/// archipelago from the tables instead.
+pub(crate) fn with_archipelago<R>(
+    settings: &NavigationSettings,
+    f: impl FnOnce(&mut CachedArchipelago) -> R,
+) -> R {
+    let mut cache = ARCHIPELAGOS
+        .with_borrow_mut(|archipelagos| archipelagos.remove(&settings.world_id))
+        .unwrap_or_else(|| CachedArchipelago::new(settings));
+    cache.archipelago.archipelago_options = settings.into();
+
+    let result = f(&mut cache);
+
+    ARCHIPELAGOS.with_borrow_mut(|archipelagos| {
+        archipelagos.insert(settings.world_id, cache);
+    });
+    result
+}
+
+/// Drops the cached archipelago of a world, forcing it to be rebuilt from the
+/// tables on the next navigation tick. Agents will recompute their paths.
+/// Called when a world is deleted.
+pub fn invalidate_archipelago(world_id: WorldId) {
+    ARCHIPELAGOS.with_borrow_mut(|archipelagos| {
+        archipelagos.remove(&world_id);
+    });
+}
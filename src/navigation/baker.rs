@@ -0,0 +1,522 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bon::Builder;
+use parry3d::{bounding_volume::Aabb, math::Pose3, query::Ray};
+use spacetimedb::ReducerContext;
+
+use crate::{
+    collisions::{
+        Collider, ColliderId, RigidBody, RigidBodyType, shape_wrapper::ShapeWrapper,
+        tick::build_bvh,
+    },
+    math::Vec3,
+    navigation::{ExternalNavMesh, NavMesh, NavMeshError, import_external_navmesh},
+    utils::WorldEntity,
+    world::{World, WorldId},
+};
+
+/// The parameters of a navigation mesh bake.
+#[derive(Builder, Clone, Copy, Debug)]
+pub struct NavMeshBakeSettings {
+    /// The horizontal size of a voxel. Smaller cells follow the geometry more
+    /// closely but produce more polygons.
+    #[builder(default = 0.3)]
+    pub cell_size: f32,
+    /// The radius of the agents, walkable areas are shrunk by it so that agents
+    /// do not clip into walls.
+    #[builder(default = 0.5)]
+    pub agent_radius: f32,
+    /// The height of the agents, surfaces with less free space above are not walkable.
+    #[builder(default = 2.0)]
+    pub agent_height: f32,
+    /// The steepest walkable slope, in degrees.
+    #[builder(default = 45.0)]
+    pub max_slope_degrees: f32,
+    /// The highest ledge agents can step up or down.
+    #[builder(default = 0.4)]
+    pub step_height: f32,
+    /// The minimum corner of the baked area. Defaults to the bounds of the static
+    /// geometry, planes excluded.
+    pub bounds_min: Option<Vec3>,
+    /// The maximum corner of the baked area.
+    pub bounds_max: Option<Vec3>,
+}
+
+impl NavMeshBakeSettings {
+    /// Checks that the settings can be baked with: sizes must be finite, the cell
+    /// size and agent height positive, the slope within 0 to 90 degrees and the
+    /// bounds, if given, finite and ordered.
+    pub fn validate(&self) -> Result<(), NavMeshBakeError> {
+        let checks = [
+            (
+                "cell_size",
+                self.cell_size.is_finite() && self.cell_size > 0.0,
+            ),
+            (
+                "agent_radius",
+                self.agent_radius.is_finite() && self.agent_radius >= 0.0,
+            ),
+            (
+                "agent_height",
+                self.agent_height.is_finite() && self.agent_height > 0.0,
+            ),
+            (
+                "max_slope_degrees",
+                (0.0..=90.0).contains(&self.max_slope_degrees),
+            ),
+            (
+                "step_height",
+                self.step_height.is_finite() && self.step_height >= 0.0,
+            ),
+            (
+                "bounds_min",
+                self.bounds_min
+                    .is_none_or(|min| min.x.is_finite() && min.y.is_finite() && min.z.is_finite()),
+            ),
+            (
+                "bounds_max",
+                self.bounds_max.is_none_or(|max| {
+                    max.x.is_finite()
+                        && max.y.is_finite()
+                        && max.z.is_finite()
+                        && self
+                            .bounds_min
+                            .is_none_or(|min| max.x > min.x && max.y >= min.y && max.z > min.z)
+                }),
+            ),
+        ];
+
+        match checks.into_iter().find(|(_, valid)| !valid) {
+            Some((setting, _)) => Err(NavMeshBakeError::InvalidSettings(setting)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The reasons a navigation mesh cannot be baked.
+#[derive(Debug)]
+pub enum NavMeshBakeError {
+    /// The world does not exist.
+    WorldNotFound(WorldId),
+    /// The named setting is out of range or not finite.
+    InvalidSettings(&'static str),
+    /// The world has no static rigid body.
+    NoStaticGeometry,
+    /// The static geometry is only made of infinite planes and no bounds were given.
+    Unbounded,
+    /// No walkable surface was found.
+    Empty,
+    /// The baked navigation mesh is invalid.
+    NavMesh(NavMeshError),
+}
+
+impl From<NavMeshError> for NavMeshBakeError {
+    fn from(err: NavMeshError) -> Self {
+        NavMeshBakeError::NavMesh(err)
+    }
+}
+
+impl Display for NavMeshBakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavMeshBakeError::WorldNotFound(id) => write!(f, "World#{id} not found"),
+            NavMeshBakeError::InvalidSettings(setting) => {
+                write!(f, "Invalid bake setting: {setting}")
+            }
+            NavMeshBakeError::NoStaticGeometry => write!(f, "No static geometry to bake"),
+            NavMeshBakeError::Unbounded => write!(f, "Static geometry is unbounded"),
+            NavMeshBakeError::Empty => write!(f, "No walkable surface found"),
+            NavMeshBakeError::NavMesh(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// A walkable voxel: the top of a solid column with enough free space above.
+struct Cell {
+    x: usize,
+    z: usize,
+    y: f32,
+}
+
+/// Bakes a navigation mesh from the static rigid bodies of the world.
+///
+/// This is a simplified Recast pipeline: the bounds are split in columns of
+/// `cell_size`, and every static shape is raycast from above and below to find
+/// the solid spans of each column. The tops of the spans that are flat enough and
+/// have `agent_height` of free space above are walkable. Neighbouring walkable
+/// cells are connected if their height difference is at most `step_height`, the
+/// walkable area is then eroded by `agent_radius` and the remaining cells are
+/// merged into rectangles of connected cells at the same height.
+pub fn bake_navmesh(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    settings: &NavMeshBakeSettings,
+) -> Result<ExternalNavMesh, NavMeshBakeError> {
+    settings.validate()?;
+    let world = World::find(ctx, world_id).ok_or(NavMeshBakeError::WorldNotFound(world_id))?;
+    let colliders: HashMap<ColliderId, ShapeWrapper> = Collider::iter(ctx, world.id)
+        .map(|collider| (collider.id, ShapeWrapper::from(&collider)))
+        .collect();
+    let bodies: Vec<RigidBody> = RigidBody::iter(ctx, world.id)
+        .filter(|body| {
+            body.body_type == RigidBodyType::Static && colliders.contains_key(&body.collider_id)
+        })
+        .collect();
+    if bodies.is_empty() {
+        return Err(NavMeshBakeError::NoStaticGeometry);
+    }
+
+    let (min, max) = bake_bounds(&bodies, &colliders, settings)?;
+    let cells = find_walkable_cells(&world, &bodies, &colliders, settings, min, max);
+    let cells = erode(cells, settings);
+    if cells.is_empty() {
+        return Err(NavMeshBakeError::Empty);
+    }
+
+    Ok(build_polygons(&cells, settings, min))
+}
+
+/// Bakes a navigation mesh from the static rigid bodies of the world and imports
+/// it as a [`crate::navigation::NavMesh`] row.
+pub fn bake_and_import_navmesh(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    settings: &NavMeshBakeSettings,
+) -> Result<NavMesh, NavMeshBakeError> {
+    let navmesh = bake_navmesh(ctx, world_id, settings)?;
+    Ok(import_external_navmesh(ctx, world_id, navmesh)?)
+}
+
+fn bake_bounds(
+    bodies: &[RigidBody],
+    colliders: &HashMap<ColliderId, ShapeWrapper>,
+    settings: &NavMeshBakeSettings,
+) -> Result<(Vec3, Vec3), NavMeshBakeError> {
+    if let (Some(min), Some(max)) = (settings.bounds_min, settings.bounds_max) {
+        return Ok((min, max));
+    }
+
+    let aabb = bodies
+        .iter()
+        .filter_map(|body| {
+            let shape = colliders.get(&body.collider_id)?;
+            if matches!(shape, ShapeWrapper::Plane(_)) {
+                return None;
+            }
+            Some(shape.collision_aabb(&body.into(), 0.0))
+        })
+        .reduce(|a, b| a.merged(&b))
+        .ok_or(NavMeshBakeError::Unbounded)?;
+
+    Ok((
+        settings.bounds_min.unwrap_or(aabb.mins.into()),
+        settings.bounds_max.unwrap_or(aabb.maxs.into()),
+    ))
+}
+
+/// Raycasts every column of the bounds against the static shapes and returns the
+/// walkable cells, grouped by column.
+fn find_walkable_cells(
+    world: &World,
+    bodies: &[RigidBody],
+    colliders: &HashMap<ColliderId, ShapeWrapper>,
+    settings: &NavMeshBakeSettings,
+    min: Vec3,
+    max: Vec3,
+) -> Vec<Cell> {
+    let (bvh, _) = build_bvh(bodies, colliders, world);
+    let min_normal_y = settings.max_slope_degrees.to_radians().cos();
+    let columns_x = ((max.x - min.x) / settings.cell_size).ceil().max(1.0) as usize;
+    let columns_z = ((max.z - min.z) / settings.cell_size).ceil().max(1.0) as usize;
+    let top = max.y + settings.agent_height;
+    let bottom = min.y - 1.0;
+    let ray_length = top - bottom;
+
+    let mut cells = Vec::new();
+    for x in 0..columns_x {
+        for z in 0..columns_z {
+            let center_x = min.x + (x as f32 + 0.5) * settings.cell_size;
+            let center_z = min.z + (z as f32 + 0.5) * settings.cell_size;
+            let column = Aabb::new(
+                Vec3::new(center_x, bottom, center_z).into(),
+                Vec3::new(center_x, top, center_z).into(),
+            );
+            let down = Ray::new(
+                Vec3::new(center_x, top, center_z).into(),
+                Vec3::new(0.0, -1.0, 0.0).into(),
+            );
+            let up = Ray::new(
+                Vec3::new(center_x, bottom, center_z).into(),
+                Vec3::new(0.0, 1.0, 0.0).into(),
+            );
+
+            // The solid spans of the column (bottom, top) and whether their top is
+            // flat enough to walk on.
+            let spans: Vec<(f32, f32, bool)> = bvh
+                .intersect_aabb(&column)
+                .filter_map(|leaf_idx| {
+                    let body = &bodies[leaf_idx as usize];
+                    let shape = colliders.get(&body.collider_id)?;
+                    let pose: Pose3 = body.into();
+                    let top_hit = shape.cast_ray_and_get_normal(&pose, &down, ray_length, true)?;
+                    let bottom_hit = shape.cast_ray_and_get_normal(&pose, &up, ray_length, true);
+                    let span_top = top - top_hit.time_of_impact;
+                    let span_bottom = bottom_hit.map_or(bottom, |hit| bottom + hit.time_of_impact);
+                    let normal: Vec3 = top_hit.normal.into();
+                    Some((span_bottom, span_top, normal.y >= min_normal_y))
+                })
+                .collect();
+
+            for (_, span_top, walkable) in &spans {
+                if !walkable || *span_top < min.y || *span_top > max.y {
+                    continue;
+                }
+
+                let blocked = spans.iter().any(|(other_bottom, other_top, _)| {
+                    *other_top > *span_top && *other_bottom < *span_top + settings.agent_height
+                });
+                if !blocked {
+                    cells.push(Cell { x, z, y: *span_top });
+                }
+            }
+        }
+    }
+
+    cells
+}
+
+/// Returns the cells of the neighbouring column in the given direction that an
+/// agent can step to from `cell`.
+fn neighbour(
+    cells: &[Cell],
+    columns: &HashMap<(usize, usize), Vec<usize>>,
+    cell: usize,
+    (dx, dz): (isize, isize),
+    step_height: f32,
+) -> Option<usize> {
+    let x = cells[cell].x.checked_add_signed(dx)?;
+    let z = cells[cell].z.checked_add_signed(dz)?;
+    columns
+        .get(&(x, z))?
+        .iter()
+        .copied()
+        .filter(|other| (cells[*other].y - cells[cell].y).abs() <= step_height)
+        .min_by(|a, b| {
+            let dy_a = (cells[*a].y - cells[cell].y).abs();
+            let dy_b = (cells[*b].y - cells[cell].y).abs();
+            dy_a.total_cmp(&dy_b)
+        })
+}
+
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+fn index_columns(cells: &[Cell]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut columns: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (index, cell) in cells.iter().enumerate() {
+        columns.entry((cell.x, cell.z)).or_default().push(index);
+    }
+    columns
+}
+
+/// Removes the cells closer than the agent radius to the border of the walkable area.
+fn erode(cells: Vec<Cell>, settings: &NavMeshBakeSettings) -> Vec<Cell> {
+    let erosion = (settings.agent_radius / settings.cell_size).ceil() as usize;
+    if erosion == 0 {
+        return cells;
+    }
+
+    let columns = index_columns(&cells);
+    let mut distances = vec![usize::MAX; cells.len()];
+    let mut frontier = Vec::new();
+    for cell in 0..cells.len() {
+        let is_border = DIRECTIONS.iter().any(|direction| {
+            neighbour(&cells, &columns, cell, *direction, settings.step_height).is_none()
+        });
+        if is_border {
+            distances[cell] = 0;
+            frontier.push(cell);
+        }
+    }
+
+    let mut distance = 0;
+    while !frontier.is_empty() && distance < erosion {
+        distance += 1;
+        let mut next = Vec::new();
+        for cell in frontier {
+            for direction in DIRECTIONS {
+                if let Some(other) =
+                    neighbour(&cells, &columns, cell, direction, settings.step_height)
+                    && distances[other] == usize::MAX
+                {
+                    distances[other] = distance;
+                    next.push(other);
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    cells
+        .into_iter()
+        .zip(distances)
+        .filter(|(_, distance)| *distance >= erosion)
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+fn find_root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (root_a, root_b) = (find_root(parents, a), find_root(parents, b));
+    parents[root_a] = root_b;
+}
+
+/// The largest height difference between cells merged into the same polygon.
+const PLANE_HEIGHT_TOLERANCE: f32 = 1e-3;
+
+/// Greedily merges the cells into rectangles of connected cells at the same
+/// height, scanning rows along X then growing each rectangle along Z. Returns the
+/// rectangles as rows of cell indices.
+fn merge_rectangles(
+    cells: &[Cell],
+    columns: &HashMap<(usize, usize), Vec<usize>>,
+    step_height: f32,
+) -> Vec<Vec<Vec<usize>>> {
+    let mut order: Vec<usize> = (0..cells.len()).collect();
+    order.sort_by_key(|cell| (cells[*cell].z, cells[*cell].x));
+
+    let mut used = vec![false; cells.len()];
+    let mut rectangles = Vec::new();
+    for start in order {
+        if used[start] {
+            continue;
+        }
+
+        let height = cells[start].y;
+        let mergeable = |used: &[bool], cell: usize| {
+            !used[cell] && (cells[cell].y - height).abs() <= PLANE_HEIGHT_TOLERANCE
+        };
+        // Extends a row along X from its first cell.
+        let grow_row = |used: &[bool], first: usize, width: Option<usize>| {
+            let mut row = vec![first];
+            while width.is_none_or(|width| row.len() < width) {
+                match neighbour(cells, columns, row[row.len() - 1], (1, 0), step_height) {
+                    Some(next) if mergeable(used, next) => row.push(next),
+                    _ => break,
+                }
+            }
+            row
+        };
+
+        let first_row = grow_row(&used, start, None);
+        first_row.iter().for_each(|cell| used[*cell] = true);
+        let width = first_row.len();
+        let mut rows = vec![first_row];
+
+        // Grows the rectangle along Z while the next row is a full, connected run
+        // of mergeable cells above the previous one.
+        loop {
+            let previous = rows.last().unwrap();
+            let above: Option<Vec<usize>> = previous
+                .iter()
+                .map(|cell| {
+                    neighbour(cells, columns, *cell, (0, 1), step_height)
+                        .filter(|next| mergeable(&used, *next))
+                })
+                .collect();
+            let Some(above) = above else {
+                break;
+            };
+            if grow_row(&used, above[0], Some(width)) != above {
+                break;
+            }
+
+            above.iter().for_each(|cell| used[*cell] = true);
+            rows.push(above);
+        }
+
+        rectangles.push(rows);
+    }
+
+    rectangles
+}
+
+/// Turns the cells into polygons, one per rectangle of merged cells. The corners
+/// of connected neighbouring cells are merged into a single vertex placed at
+/// their average height, so that landmass connects the polygons. Rectangles keep
+/// a vertex at every cell corner along their edges, so that their edges match
+/// the edges of the smaller polygons next to them.
+fn build_polygons(cells: &[Cell], settings: &NavMeshBakeSettings, min: Vec3) -> ExternalNavMesh {
+    // Corners of a cell, counter-clockwise in the (X, Z) plane, i.e. in landmass's
+    // Z-up frame once `XYZ` swaps Y and Z. `XYZ` being left-handed (X right, Y up,
+    // Z forward), this is also counter-clockwise seen from above in the engine:
+    // 0 = (-x, -z), 1 = (+x, -z), 2 = (+x, +z), 3 = (-x, +z).
+    let columns = index_columns(cells);
+    let mut parents: Vec<usize> = (0..cells.len() * 4).collect();
+
+    for cell in 0..cells.len() {
+        if let Some(other) = neighbour(cells, &columns, cell, (1, 0), settings.step_height) {
+            union(&mut parents, cell * 4 + 1, other * 4);
+            union(&mut parents, cell * 4 + 2, other * 4 + 3);
+        }
+        if let Some(other) = neighbour(cells, &columns, cell, (0, 1), settings.step_height) {
+            union(&mut parents, cell * 4 + 3, other * 4);
+            union(&mut parents, cell * 4 + 2, other * 4 + 1);
+        }
+    }
+
+    let mut heights: HashMap<usize, (f32, u32)> = HashMap::new();
+    for corner in 0..cells.len() * 4 {
+        let root = find_root(&mut parents, corner);
+        let entry = heights.entry(root).or_insert((0.0, 0));
+        entry.0 += cells[corner / 4].y;
+        entry.1 += 1;
+    }
+
+    const CORNER_OFFSETS: [(usize, usize); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+    let mut vertices = Vec::new();
+    let mut vertex_ids: HashMap<usize, u64> = HashMap::new();
+    let mut vertex = |cell: usize, corner: usize| {
+        let root = find_root(&mut parents, cell * 4 + corner);
+        *vertex_ids.entry(root).or_insert_with(|| {
+            let (sum, count) = heights[&root];
+            let (dx, dz) = CORNER_OFFSETS[corner];
+            vertices.push(Vec3::new(
+                min.x + (cells[cell].x + dx) as f32 * settings.cell_size,
+                sum / count as f32,
+                min.z + (cells[cell].z + dz) as f32 * settings.cell_size,
+            ));
+            vertices.len() as u64 - 1
+        })
+    };
+
+    let rectangles = merge_rectangles(cells, &columns, settings.step_height);
+    let mut polygons = Vec::with_capacity(rectangles.len());
+    for rows in rectangles {
+        let (first, last) = (&rows[0], &rows[rows.len() - 1]);
+        let width = first.len();
+        // Walks the border counter-clockwise: along -Z, +X, +Z then -X.
+        let mut polygon = Vec::with_capacity(2 * (width + rows.len()));
+        polygon.extend(first.iter().map(|cell| vertex(*cell, 0)));
+        polygon.extend(rows.iter().map(|row| vertex(row[width - 1], 1)));
+        polygon.extend(last.iter().rev().map(|cell| vertex(*cell, 2)));
+        polygon.push(vertex(last[0], 3));
+        polygon.extend(rows[1..].iter().rev().map(|row| vertex(row[0], 0)));
+        polygons.push(polygon);
+    }
+
+    ExternalNavMesh {
+        translation: Vec3::ZERO,
+        rotation: 0.0,
+        vertices,
+        polygon_type_indices: vec![0; polygons.len()],
+        polygons,
+        height_mesh: None,
+    }
+}
@@ -1,9 +1,12 @@
 use bon::Builder;
-use landmass::NavigationMesh;
+use landmass::{HeightNavigationMesh, HeightPolygon, NavigationMesh};
 use serde::Deserialize;
 use spacetimedb::SpacetimeType;
 
-use crate::{math::Vec3, navigation::coordinates::XYZ};
+use crate::{
+    math::Vec3,
+    navigation::{NavMeshError, coordinates::XYZ},
+};
 
 pub type NavMeshId = u64;
 
@@ -14,6 +17,85 @@ pub struct ExternalNavMesh {
     pub vertices: Vec<Vec3>,
     pub polygons: Vec<Vec<u64>>,
     pub polygon_type_indices: Vec<u64>,
+    /// Detailed geometry used to compute accurate heights on coarse polygons,
+    /// e.g. stairs modelled as a single slope.
+    #[serde(default)]
+    pub height_mesh: Option<ExternalHeightMesh>,
+}
+
+/// The detail mesh of a navigation mesh, like Recast's detail mesh. Every polygon
+/// of the navigation mesh is covered by a set of triangles.
+#[derive(Clone, Debug, Deserialize, SpacetimeType)]
+pub struct ExternalHeightMesh {
+    /// The detail polygons, one for each polygon of the navigation mesh, in the
+    /// same order.
+    pub polygons: Vec<ExternalHeightPolygon>,
+    /// The vertices of the detail triangles.
+    pub vertices: Vec<Vec3>,
+    /// The detail triangles, as 3 vertex indices per triangle. Indices are
+    /// relative to the `base_vertex_index` of the polygon the triangle belongs to.
+    pub triangles: Vec<u8>,
+}
+
+/// The range of vertices and triangles of a [`ExternalHeightMesh`] covering a
+/// polygon of the navigation mesh.
+#[derive(Clone, Copy, Debug, Deserialize, SpacetimeType)]
+pub struct ExternalHeightPolygon {
+    pub base_vertex_index: u32,
+    pub vertex_count: u32,
+    pub base_triangle_index: u32,
+    pub triangle_count: u32,
+}
+
+impl From<HeightNavigationMesh<XYZ>> for ExternalHeightMesh {
+    fn from(height_mesh: HeightNavigationMesh<XYZ>) -> Self {
+        ExternalHeightMesh {
+            polygons: height_mesh
+                .polygons
+                .into_iter()
+                .map(|polygon| ExternalHeightPolygon {
+                    base_vertex_index: polygon.base_vertex_index,
+                    vertex_count: polygon.vertex_count,
+                    base_triangle_index: polygon.base_triangle_index,
+                    triangle_count: polygon.triangle_count,
+                })
+                .collect(),
+            vertices: height_mesh.vertices,
+            triangles: height_mesh.triangles.into_iter().flatten().collect(),
+        }
+    }
+}
+
+impl TryFrom<ExternalHeightMesh> for HeightNavigationMesh<XYZ> {
+    type Error = NavMeshError;
+
+    fn try_from(height_mesh: ExternalHeightMesh) -> Result<Self, Self::Error> {
+        if height_mesh.triangles.len() % 3 != 0 {
+            return Err(NavMeshError::InvalidHeightMesh(format!(
+                "{} triangle indices is not a multiple of 3",
+                height_mesh.triangles.len()
+            )));
+        }
+
+        Ok(HeightNavigationMesh {
+            polygons: height_mesh
+                .polygons
+                .into_iter()
+                .map(|polygon| HeightPolygon {
+                    base_vertex_index: polygon.base_vertex_index,
+                    vertex_count: polygon.vertex_count,
+                    base_triangle_index: polygon.base_triangle_index,
+                    triangle_count: polygon.triangle_count,
+                })
+                .collect(),
+            vertices: height_mesh.vertices,
+            triangles: height_mesh
+                .triangles
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+        })
+    }
 }
 
 impl From<NavigationMesh<XYZ>> for ExternalNavMesh {
@@ -47,12 +129,25 @@ impl From<NavigationMesh<XYZ>> for ExternalNavMesh {
             vertices,
             polygons,
             polygon_type_indices,
+            height_mesh: nav_mesh.height_mesh.map(Into::into),
         }
     }
 }
 
-impl From<ExternalNavMesh> for NavigationMesh<XYZ> {
-    fn from(st_nav_mesh: ExternalNavMesh) -> Self {
+impl TryFrom<ExternalNavMesh> for NavigationMesh<XYZ> {
+    type Error = NavMeshError;
+
+    fn try_from(st_nav_mesh: ExternalNavMesh) -> Result<Self, Self::Error> {
+        if let Some(height_mesh) = &st_nav_mesh.height_mesh
+            && height_mesh.polygons.len() != st_nav_mesh.polygons.len()
+        {
+            return Err(NavMeshError::InvalidHeightMesh(format!(
+                "{} height polygons for {} polygons",
+                height_mesh.polygons.len(),
+                st_nav_mesh.polygons.len()
+            )));
+        }
+
         let polygons = st_nav_mesh
             .polygons
             .into_iter()
@@ -65,11 +160,11 @@ impl From<ExternalNavMesh> for NavigationMesh<XYZ> {
             .map(|idx| idx as usize)
             .collect();
 
-        NavigationMesh {
+        Ok(NavigationMesh {
             vertices: st_nav_mesh.vertices,
             polygons,
             polygon_type_indices,
-            height_mesh: None,
-        }
+            height_mesh: st_nav_mesh.height_mesh.map(TryInto::try_into).transpose()?,
+        })
     }
 }
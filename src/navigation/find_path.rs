@@ -0,0 +1,138 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bon::Builder;
+use landmass::PathStep;
+use spacetimedb::ReducerContext;
+
+use crate::{
+    math::Vec3,
+    navigation::{
+        Archipelago, InvalidTypeIndexCost, NavigationSettings, TypeIndexCost,
+        archipelago_cache::with_archipelago,
+    },
+    world::WorldId,
+};
+
+/// The options of a [`find_path`] query.
+#[derive(Builder, Clone, Debug, Default)]
+pub struct FindPathOptions {
+    /// Overrides of the world's node type costs for this query, see
+    /// [`crate::navigation::NavNodeType`].
+    #[builder(default = Vec::new())]
+    pub type_index_costs: Vec<TypeIndexCost>,
+}
+
+/// A path computed by [`find_path`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavPath {
+    /// The corners of the path, from the start point to the end point, both
+    /// snapped onto the navigation mesh.
+    pub corners: Vec<Vec3>,
+    /// The length of the path, going through every corner.
+    pub length: f32,
+}
+
+/// The reasons a path cannot be found.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FindPathError {
+    /// The start point is not close enough to a navigation mesh.
+    StartNotOnMesh(Vec3),
+    /// The end point is not close enough to a navigation mesh.
+    EndNotOnMesh(Vec3),
+    /// The start and end points are not connected.
+    NoPath,
+    /// A node type cost override is not greater than 0.0.
+    InvalidTypeIndexCost(InvalidTypeIndexCost),
+}
+
+impl Display for FindPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FindPathError::StartNotOnMesh(point) => write!(f, "Start {point} is not on a navmesh"),
+            FindPathError::EndNotOnMesh(point) => write!(f, "End {point} is not on a navmesh"),
+            FindPathError::NoPath => write!(f, "No path found"),
+            FindPathError::InvalidTypeIndexCost(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Computes the path from `start` to `end` on the navigation meshes of the world,
+/// without creating an agent. Points are sampled onto the meshes using the world's
+/// [`NavigationSettings`]. Animation links are used like by agents, their start
+/// and end points being part of the corners. Fails if a node type cost override
+/// is not greater than 0.0.
+pub fn find_path(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    start: Vec3,
+    end: Vec3,
+    options: &FindPathOptions,
+) -> Result<NavPath, FindPathError> {
+    if let Some(cost) = options
+        .type_index_costs
+        .iter()
+        .find(|cost| !cost.is_valid())
+    {
+        return Err(FindPathError::InvalidTypeIndexCost(InvalidTypeIndexCost(
+            *cost,
+        )));
+    }
+
+    let settings = NavigationSettings::for_world(ctx, world_id);
+    let type_index_costs: HashMap<usize, f32> = options
+        .type_index_costs
+        .iter()
+        .map(|cost| (cost.type_index as usize, cost.cost))
+        .collect();
+
+    with_archipelago(&settings, |cache| {
+        cache.sync_islands(ctx, world_id);
+        cache.sync_node_types(ctx, world_id);
+        cache.sync_animation_links(ctx, world_id);
+
+        compute_path(&cache.archipelago, start, end, &type_index_costs)
+    })
+}
+
+/// Computes the path from `start` to `end` on an already synced archipelago.
+pub(crate) fn compute_path(
+    archipelago: &Archipelago,
+    start: Vec3,
+    end: Vec3,
+    type_index_costs: &HashMap<usize, f32>,
+) -> Result<NavPath, FindPathError> {
+    let sample_distance = &archipelago.archipelago_options.point_sample_distance;
+    let start_point = archipelago
+        .sample_point(start, sample_distance)
+        .map_err(|_| FindPathError::StartNotOnMesh(start))?;
+    let end_point = archipelago
+        .sample_point(end, sample_distance)
+        .map_err(|_| FindPathError::EndNotOnMesh(end))?;
+
+    let steps = archipelago
+        .find_path(&start_point, &end_point, type_index_costs)
+        .map_err(|_| FindPathError::NoPath)?;
+
+    let mut corners = vec![start_point.point()];
+    for step in steps {
+        match step {
+            PathStep::Walk(point) => corners.push(point),
+            PathStep::AnimationLink {
+                start_point,
+                end_point,
+                ..
+            } => {
+                corners.push(start_point);
+                corners.push(end_point);
+            }
+        }
+    }
+    corners.dedup();
+
+    let length = corners
+        .windows(2)
+        .map(|segment| segment[0].distance(&segment[1]))
+        .sum();
+
+    Ok(NavPath { corners, length })
+}
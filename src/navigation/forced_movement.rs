@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{
+    math::Vec3,
+    navigation::{NavigationAgent, NavigationAgentId},
+    spells::PendingCombatEvent,
+    utils::{WorldEntity, now_ms},
+    world::WorldId,
+};
+
+pub type ForcedMovementId = u64;
+
+#[table(accessor = steng_navigation_forced_movement)]
+#[derive(Clone, Debug)]
+/// Moves an agent from one point to another over a duration, ignoring its path,
+/// e.g. knockbacks and teleports. The agent is kept on the navigation mesh: the
+/// movement stops where its path from the agent's position would leave the mesh.
+/// Instant movements (teleports) are not clamped, they only need to end on a mesh.
+pub struct ForcedMovement {
+    #[primary_key]
+    #[auto_inc]
+    pub id: ForcedMovementId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    /// The agent being moved. An agent has at most one forced movement.
+    #[unique]
+    pub agent_id: NavigationAgentId,
+    /// The position of the agent when the movement started.
+    pub from: Vec3,
+    /// The position the agent is moved to.
+    pub to: Vec3,
+    pub started_at_ms: i64,
+    /// When the agent reaches `to`. Equal to `started_at_ms` for instant movements.
+    pub ends_at_ms: i64,
+    /// The combat event recorded once the movement actually moved the agent, for
+    /// movements caused by a spell.
+    pub combat_event: Option<PendingCombatEvent>,
+}
+
+impl WorldEntity for ForcedMovement {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_forced_movement().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_forced_movement().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_navigation_forced_movement()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_navigation_forced_movement()
+            .world_id()
+            .filter(world_id)
+            .map(|movement| (movement.id, movement))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_navigation_forced_movement()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_forced_movement().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db
+            .steng_navigation_forced_movement()
+            .id()
+            .delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_navigation_forced_movement()
+            .world_id()
+            .filter(world_id)
+            .for_each(|movement| {
+                ctx.db
+                    .steng_navigation_forced_movement()
+                    .id()
+                    .delete(movement.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_navigation_forced_movement()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl ForcedMovement {
+    /// Returns the position of the movement at the given time.
+    pub fn position_at(&self, time_ms: i64) -> Vec3 {
+        if time_ms >= self.ends_at_ms {
+            return self.to;
+        }
+
+        let t =
+            (time_ms - self.started_at_ms) as f32 / (self.ends_at_ms - self.started_at_ms) as f32;
+        self.from.lerp(&self.to, t.clamp(0.0, 1.0))
+    }
+
+    /// Returns the velocity of the moved agent, zero for instant movements.
+    pub fn velocity(&self) -> Vec3 {
+        if self.ends_at_ms <= self.started_at_ms {
+            return Vec3::ZERO;
+        }
+
+        (self.to - self.from) / ((self.ends_at_ms - self.started_at_ms) as f32 / 1000.0)
+    }
+
+    /// Returns true if the movement is over at the given time.
+    pub fn is_finished(&self, time_ms: i64) -> bool {
+        time_ms >= self.ends_at_ms
+    }
+}
+
+/// Moves the agent to `to` over `duration_ms`, replacing any forced movement it
+/// already has. The movement is applied by the navigation tick.
+pub fn force_move_agent(
+    ctx: &ReducerContext,
+    agent: &NavigationAgent,
+    to: Vec3,
+    duration_ms: i32,
+) -> ForcedMovement {
+    if let Some(existing) = ctx
+        .db
+        .steng_navigation_forced_movement()
+        .agent_id()
+        .find(agent.id())
+    {
+        existing.delete(ctx);
+    }
+
+    let now = now_ms(ctx);
+    ForcedMovement {
+        id: 0,
+        world_id: agent.world_id(),
+        agent_id: agent.id(),
+        from: agent.position(),
+        to,
+        started_at_ms: now,
+        ends_at_ms: now + duration_ms.max(0) as i64,
+        combat_event: None,
+    }
+    .insert(ctx)
+}
+
+/// Pushes the agent `distance` units along `direction` over `duration_ms`.
+pub fn knockback_agent(
+    ctx: &ReducerContext,
+    agent: &NavigationAgent,
+    direction: Vec3,
+    distance: f32,
+    duration_ms: i32,
+) -> ForcedMovement {
+    let to = agent.position() + direction.normalize() * distance;
+    force_move_agent(ctx, agent, to, duration_ms)
+}
+
+/// Teleports the agent to the closest point of the navigation mesh near `to`
+/// on the next navigation tick.
+pub fn teleport_agent(ctx: &ReducerContext, agent: &NavigationAgent, to: Vec3) -> ForcedMovement {
+    force_move_agent(ctx, agent, to, 0)
+}
@@ -0,0 +1,341 @@
+use std::{collections::HashMap, fmt::Display};
+
+use gltf::{Gltf, buffer::Source, scene::Node};
+use spacetimedb::{ReducerContext, SpacetimeType};
+
+use crate::{
+    math::Vec3,
+    navigation::{ExternalNavMesh, NavMesh, NavMeshError, NavNodeType, import_external_navmesh},
+    utils::WorldEntity,
+    world::WorldId,
+};
+
+/// Vertices closer than this are merged, connecting polygons that do not share
+/// vertex indices in the source file (e.g. glTF primitives of different materials).
+const WELD_PRECISION: f32 = 1e-4;
+
+/// The file formats navigation meshes can be imported from.
+///
+/// Both formats are right-handed and Y-up, with walkable faces wound
+/// counter-clockwise when seen from above, as exported by most modelling tools.
+/// Faces are reversed on import to match the engine's left-handed convention
+/// (X right, Y up, Z forward).
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub enum NavMeshFormat {
+    /// Wavefront OBJ text.
+    Obj,
+    /// glTF JSON or GLB binary. Buffers must be embedded in the GLB binary chunk.
+    Gltf,
+}
+
+/// The reasons a navigation mesh file cannot be imported.
+#[derive(Debug)]
+pub enum NavMeshImportError {
+    /// The OBJ text is invalid at the given line (1-based).
+    InvalidObj { line: usize, message: String },
+    /// The glTF data is invalid.
+    InvalidGltf(String),
+    /// The file does not contain any polygon.
+    Empty,
+    /// The imported navigation mesh is invalid.
+    NavMesh(NavMeshError),
+}
+
+impl From<NavMeshError> for NavMeshImportError {
+    fn from(err: NavMeshError) -> Self {
+        NavMeshImportError::NavMesh(err)
+    }
+}
+
+impl Display for NavMeshImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavMeshImportError::InvalidObj { line, message } => {
+                write!(f, "Invalid OBJ at line {line}: {message}")
+            }
+            NavMeshImportError::InvalidGltf(message) => write!(f, "Invalid glTF: {message}"),
+            NavMeshImportError::Empty => write!(f, "No polygon found"),
+            NavMeshImportError::NavMesh(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Accumulates polygons, merging identical vertices.
+#[derive(Default)]
+struct NavMeshBuilder {
+    vertices: Vec<Vec3>,
+    welded: HashMap<(i64, i64, i64), u64>,
+    polygons: Vec<Vec<u64>>,
+    polygon_type_indices: Vec<u64>,
+}
+
+impl NavMeshBuilder {
+    fn vertex(&mut self, vertex: Vec3) -> u64 {
+        let key = (
+            (vertex.x / WELD_PRECISION).round() as i64,
+            (vertex.y / WELD_PRECISION).round() as i64,
+            (vertex.z / WELD_PRECISION).round() as i64,
+        );
+        *self.welded.entry(key).or_insert_with(|| {
+            self.vertices.push(vertex);
+            self.vertices.len() as u64 - 1
+        })
+    }
+
+    /// Adds a face of a right-handed Y-up file. Its winding is reversed so that
+    /// faces counter-clockwise seen from above in the file are counter-clockwise
+    /// in landmass's Z-up frame, where `XYZ` maps Z forward to Y.
+    fn polygon(&mut self, vertices: &[Vec3], type_index: u64) {
+        let mut polygon: Vec<u64> = vertices.iter().rev().map(|v| self.vertex(*v)).collect();
+        polygon.dedup();
+        if polygon.len() > 1 && polygon.first() == polygon.last() {
+            polygon.pop();
+        }
+        // Degenerate polygons are rejected by the navmesh validation.
+        if polygon.len() < 3 {
+            return;
+        }
+
+        self.polygons.push(polygon);
+        self.polygon_type_indices.push(type_index);
+    }
+
+    fn build(self) -> Result<ExternalNavMesh, NavMeshImportError> {
+        if self.polygons.is_empty() {
+            return Err(NavMeshImportError::Empty);
+        }
+
+        Ok(ExternalNavMesh {
+            translation: Vec3::ZERO,
+            rotation: 0.0,
+            vertices: self.vertices,
+            polygons: self.polygons,
+            polygon_type_indices: self.polygon_type_indices,
+            height_mesh: None,
+        })
+    }
+}
+
+/// Builds a navigation mesh from Wavefront OBJ text. Every face becomes a polygon
+/// whose type index is looked up in `type_indices` by the name of its material
+/// (`usemtl`), falling back to its group or object name (`g`, `o`), and to 0.
+/// Faces must be counter-clockwise seen from above, see [`NavMeshFormat`].
+pub fn navmesh_from_obj(
+    obj: &str,
+    type_indices: &HashMap<String, u64>,
+) -> Result<ExternalNavMesh, NavMeshImportError> {
+    let mut builder = NavMeshBuilder::default();
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut material: Option<&str> = None;
+    let mut group: Option<&str> = None;
+
+    for (line_index, line) in obj.lines().enumerate() {
+        let invalid = |message: &str| NavMeshImportError::InvalidObj {
+            line: line_index + 1,
+            message: message.to_string(),
+        };
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let coords = tokens
+                    .take(3)
+                    .map(|token| token.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid("invalid vertex coordinate"))?;
+                let [x, y, z] = coords[..] else {
+                    return Err(invalid("a vertex needs 3 coordinates"));
+                };
+                positions.push(Vec3::new(x, y, z));
+            }
+            Some("f") => {
+                let face = tokens
+                    .map(|token| {
+                        // Faces reference vertices as `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+                        let index = token
+                            .split('/')
+                            .next()
+                            .and_then(|index| index.parse::<i64>().ok())
+                            .ok_or_else(|| invalid("invalid face vertex"))?;
+                        // Negative indices are relative to the last vertex.
+                        let index = if index < 0 {
+                            positions.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        positions
+                            .get(
+                                usize::try_from(index)
+                                    .map_err(|_| invalid("vertex out of range"))?,
+                            )
+                            .copied()
+                            .ok_or_else(|| invalid("vertex out of range"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let type_index = material
+                    .or(group)
+                    .and_then(|name| type_indices.get(name))
+                    .copied()
+                    .unwrap_or(0);
+                builder.polygon(&face, type_index);
+            }
+            Some("usemtl") => material = tokens.next(),
+            Some("g") | Some("o") => group = tokens.next(),
+            _ => {}
+        }
+    }
+
+    builder.build()
+}
+
+/// Builds a navigation mesh from glTF JSON or GLB binary data. Every triangle of
+/// the meshes of the default scene (or of every node if there is no scene)
+/// becomes a polygon, with node transforms applied. The type index of a triangle
+/// is looked up in `type_indices` by the name of its material, falling back to
+/// the name of its mesh, and to 0. Triangles must be counter-clockwise seen from
+/// above, as required by the glTF specification for front faces, see
+/// [`NavMeshFormat`].
+pub fn navmesh_from_gltf(
+    bytes: &[u8],
+    type_indices: &HashMap<String, u64>,
+) -> Result<ExternalNavMesh, NavMeshImportError> {
+    let gltf =
+        Gltf::from_slice(bytes).map_err(|err| NavMeshImportError::InvalidGltf(err.to_string()))?;
+    let mut builder = NavMeshBuilder::default();
+
+    let roots: Vec<Node> = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => scene.nodes().collect(),
+        None => gltf.nodes().collect(),
+    };
+    for node in roots {
+        add_gltf_node(&gltf, &node, IDENTITY, type_indices, &mut builder)?;
+    }
+
+    builder.build()
+}
+
+type Matrix = [[f32; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Multiplies two column-major matrices.
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for (col, result_col) in result.iter_mut().enumerate() {
+        for (row, value) in result_col.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+        }
+    }
+    result
+}
+
+fn transform_point(matrix: &Matrix, point: [f32; 3]) -> Vec3 {
+    let [x, y, z] = point;
+    Vec3::new(
+        matrix[0][0] * x + matrix[1][0] * y + matrix[2][0] * z + matrix[3][0],
+        matrix[0][1] * x + matrix[1][1] * y + matrix[2][1] * z + matrix[3][1],
+        matrix[0][2] * x + matrix[1][2] * y + matrix[2][2] * z + matrix[3][2],
+    )
+}
+
+fn add_gltf_node(
+    gltf: &Gltf,
+    node: &Node,
+    parent: Matrix,
+    type_indices: &HashMap<String, u64>,
+    builder: &mut NavMeshBuilder,
+) -> Result<(), NavMeshImportError> {
+    let transform = mul(&parent, &node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| match buffer.source() {
+                Source::Bin => gltf.blob.as_deref(),
+                Source::Uri(_) => None,
+            });
+            let positions: Vec<Vec3> = reader
+                .read_positions()
+                .ok_or_else(|| {
+                    NavMeshImportError::InvalidGltf(format!(
+                        "primitive of mesh {} has no readable positions",
+                        mesh.index()
+                    ))
+                })?
+                .map(|position| transform_point(&transform, position))
+                .collect();
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+
+            let type_index = primitive
+                .material()
+                .name()
+                .or(mesh.name())
+                .and_then(|name| type_indices.get(name))
+                .copied()
+                .unwrap_or(0);
+
+            for triangle in indices.chunks_exact(3) {
+                let triangle = triangle
+                    .iter()
+                    .map(|index| {
+                        positions.get(*index as usize).copied().ok_or_else(|| {
+                            NavMeshImportError::InvalidGltf(format!(
+                                "index {index} out of range in mesh {}",
+                                mesh.index()
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                builder.polygon(&triangle, type_index);
+            }
+        }
+    }
+
+    for child in node.children() {
+        add_gltf_node(gltf, &child, transform, type_indices, builder)?;
+    }
+
+    Ok(())
+}
+
+/// Imports a navigation mesh file into the world, taking the bytes directly so it
+/// can be called from a reducer. Polygon types are resolved from the names of the
+/// world's [`NavNodeType`]s.
+pub fn import_navmesh_bytes(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    format: NavMeshFormat,
+    bytes: &[u8],
+    translation: Vec3,
+    rotation: f32,
+) -> Result<NavMesh, NavMeshImportError> {
+    let type_indices: HashMap<String, u64> = NavNodeType::iter(ctx, world_id)
+        .map(|node_type| (node_type.name, node_type.type_index as u64))
+        .collect();
+
+    let mut navmesh = match format {
+        NavMeshFormat::Obj => {
+            let obj = std::str::from_utf8(bytes).map_err(|err| NavMeshImportError::InvalidObj {
+                line: 0,
+                message: err.to_string(),
+            })?;
+            navmesh_from_obj(obj, &type_indices)?
+        }
+        NavMeshFormat::Gltf => navmesh_from_gltf(bytes, &type_indices)?,
+    };
+    navmesh.translation = translation;
+    navmesh.rotation = rotation;
+
+    Ok(import_external_navmesh(ctx, world_id, navmesh)?)
+}
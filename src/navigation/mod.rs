@@ -1,15 +1,40 @@
 mod agent_state;
+mod animation_link;
+mod archipelago_cache;
+mod baker;
 mod coordinates;
 mod external_navmesh;
+mod find_path;
+mod forced_movement;
+mod importers;
 mod navigation_agent;
+mod navigation_character;
+mod navigation_event;
 mod navigation_impl;
+mod navigation_path;
+mod navigation_settings;
+mod node_type;
+mod queries;
 mod target_reached_condition;
 mod utils;
 mod validated_navmesh;
 
 pub use agent_state::*;
+pub use animation_link::*;
+pub use archipelago_cache::invalidate_archipelago;
+pub use baker::*;
 pub use external_navmesh::*;
+pub use find_path::*;
+pub use forced_movement::*;
+pub use importers::*;
 pub use navigation_agent::*;
+pub use navigation_character::{NavigationCharacter, NavigationCharacterId};
+pub use navigation_event::{NavigationEvent, NavigationEventId};
 pub use navigation_impl::*;
+pub use navigation_path::{NavigationPath, NavigationPathId};
+pub use navigation_settings::*;
+pub use node_type::*;
+pub use queries::*;
 pub use target_reached_condition::*;
 pub use utils::*;
+pub use validated_navmesh::{NavMesh, NavMeshError};
@@ -5,8 +5,12 @@ use landmass::Agent;
 use spacetimedb::{ReducerContext, Table, table};
 
 use crate::{
+    collisions::RigidBodyId,
     math::Vec3,
-    navigation::{DestinationReachedCondition, NavigationState, coordinates::XYZ},
+    navigation::{
+        AnimationLinkRequest, DestinationReachedCondition, InvalidTypeIndexCost, NavigationState,
+        ReachedAnimationLink, TypeIndexCost, coordinates::XYZ,
+    },
     utils::WorldEntity,
     world::WorldId,
 };
@@ -28,6 +32,10 @@ pub struct NavigationAgent {
     /// An optional external ID for the agent. This can be used to link the
     /// agent to an entity in another system.
     pub external_id: Option<u64>,
+    /// The rigid body driven by the agent. Every navigation tick, the body is
+    /// moved to the agent's position and rotated to face its velocity, before
+    /// collisions are computed.
+    pub rigid_body_id: Option<RigidBodyId>,
     /// The current position of the agent.
     #[builder(default = Vec3::ZERO)]
     position: Vec3,
@@ -63,6 +71,20 @@ pub struct NavigationAgent {
     /// wants to go to the same place).
     #[builder(default = false)]
     paused: bool,
+    /// Whether the remaining path of the agent is exposed in the
+    /// [`crate::navigation::NavigationPath`] table.
+    #[builder(default = false)]
+    expose_path: bool,
+    /// Overrides of the world's node type costs for this agent, letting it prefer
+    /// or avoid some terrains, see [`crate::navigation::NavNodeType`].
+    #[builder(default = Vec::new())]
+    type_index_costs: Vec<TypeIndexCost>,
+    /// The animation link the agent reached or is using, if any.
+    #[builder(skip)]
+    reached_animation_link: Option<ReachedAnimationLink>,
+    /// The animation link action to apply on the next navigation tick.
+    #[builder(skip)]
+    animation_link_request: Option<AnimationLinkRequest>,
 }
 
 impl NavigationAgent {
@@ -147,6 +169,20 @@ impl NavigationAgent {
         self
     }
 
+    /// Whether the remaining path of the agent is exposed in the
+    /// [`crate::navigation::NavigationPath`] table.
+    pub fn expose_path(&self) -> bool {
+        self.expose_path
+    }
+
+    /// Sets whether the remaining path of the agent is exposed in the
+    /// [`crate::navigation::NavigationPath`] table. Worlds can expose the paths of
+    /// every agent with [`crate::navigation::NavigationSettings::expose_paths`].
+    pub fn set_expose_path(&mut self, expose_path: bool) -> &mut Self {
+        self.expose_path = expose_path;
+        self
+    }
+
     /// The current position to move towards. Modifying this every update is fine.
     /// Paths will be reused for points near each other if possible.
     /// However, swapping between two distant position every update can be
@@ -193,6 +229,103 @@ impl NavigationAgent {
         self.state = state;
         self
     }
+
+    /// The animation link the agent reached along its path or is currently using.
+    /// Only set in the [`NavigationState::ReachedAnimationLink`] and
+    /// [`NavigationState::UsingAnimationLink`] states.
+    pub fn reached_animation_link(&self) -> Option<&ReachedAnimationLink> {
+        self.reached_animation_link.as_ref()
+    }
+
+    /// Starts using the reached animation link on the next navigation tick. While
+    /// using it, the agent is no longer moved by the navigation system and the
+    /// game is responsible for moving it to the end point of the link, then
+    /// calling [`Self::finish_animation_link`].
+    pub fn start_animation_link(&mut self) -> &mut Self {
+        self.animation_link_request = Some(AnimationLinkRequest::Start);
+        self
+    }
+
+    /// Reports that the traversal of the animation link is over. The agent resumes
+    /// its path from its current position on the next navigation tick.
+    pub fn finish_animation_link(&mut self) -> &mut Self {
+        self.animation_link_request = Some(AnimationLinkRequest::Finish);
+        self
+    }
+
+    /// Applies the pending animation link request to the landmass agent.
+    pub(crate) fn apply_animation_link_request(&mut self, lm: &mut landmass::Agent<XYZ>) {
+        let result = match self.animation_link_request.take() {
+            Some(AnimationLinkRequest::Start) => {
+                lm.start_animation_link().map_err(|err| format!("{err:?}"))
+            }
+            Some(AnimationLinkRequest::Finish) => {
+                lm.end_animation_link().map_err(|err| format!("{err:?}"))
+            }
+            None => return,
+        };
+
+        if let Err(err) = result {
+            log::error!(
+                "[NavigationAgent#{}] Failed to apply animation link request: {}",
+                self.id,
+                err
+            );
+        }
+    }
+
+    pub(crate) fn set_reached_animation_link(
+        &mut self,
+        link: Option<ReachedAnimationLink>,
+    ) -> &mut Self {
+        self.reached_animation_link = link;
+        self
+    }
+
+    /// The node type costs overridden for this agent.
+    pub fn type_index_costs(&self) -> &[TypeIndexCost] {
+        &self.type_index_costs
+    }
+
+    /// Overrides the cost of a node type for this agent. The cost must be greater
+    /// than 0.0.
+    pub fn set_type_index_cost(
+        &mut self,
+        type_index: u32,
+        cost: f32,
+    ) -> Result<&mut Self, InvalidTypeIndexCost> {
+        let cost = TypeIndexCost { type_index, cost };
+        if !cost.is_valid() {
+            return Err(InvalidTypeIndexCost(cost));
+        }
+
+        self.remove_type_index_cost(type_index);
+        self.type_index_costs.push(cost);
+        Ok(self)
+    }
+
+    /// Removes the override of a node type cost, the agent uses the world's cost
+    /// again.
+    pub fn remove_type_index_cost(&mut self, type_index: u32) -> &mut Self {
+        self.type_index_costs
+            .retain(|cost| cost.type_index != type_index);
+        self
+    }
+}
+
+impl NavigationAgent {
+    /// Returns the agent of a world linked to the given external ID, if any.
+    pub fn find_by_external_id(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        external_id: u64,
+    ) -> Option<Self> {
+        ctx.db
+            .steng_navigation_agent()
+            .world_id()
+            .filter(world_id)
+            .find(|agent| agent.external_id == Some(external_id))
+    }
 }
 
 impl WorldEntity for NavigationAgent {
@@ -260,7 +393,57 @@ impl From<&NavigationAgent> for landmass::Agent<XYZ> {
         lm.current_target = value.current_target;
         lm.target_reached_condition = value.target_reached_condition.into();
         lm.state = value.state.into();
+        value.apply_type_index_costs(&mut lm);
 
         lm
     }
 }
+
+impl NavigationAgent {
+    /// Copies the row's settings onto an agent already in the archipelago,
+    /// preserving its path.
+    pub(crate) fn sync_lm_agent(&self, lm: &mut landmass::Agent<XYZ>) {
+        lm.position = self.position;
+        lm.velocity = self.velocity;
+        lm.radius = self.radius;
+        lm.desired_speed = self.desired_speed;
+        lm.max_speed = self.max_speed;
+        lm.current_target = self.current_target;
+        lm.target_reached_condition = self.target_reached_condition.into();
+        lm.paused = self.paused;
+        self.apply_type_index_costs(lm);
+    }
+
+    /// Replaces the type index cost overrides of the landmass agent with the
+    /// agent's ones if they changed. Invalid costs are ignored and only reported
+    /// when the overrides change.
+    fn apply_type_index_costs(&self, lm: &mut landmass::Agent<XYZ>) {
+        let mut current = lm.get_type_index_cost_overrides().collect::<Vec<_>>();
+        let mut wanted = self
+            .type_index_costs
+            .iter()
+            .filter(|cost| cost.is_valid())
+            .map(|cost| (cost.type_index as usize, cost.cost))
+            .collect::<Vec<_>>();
+        current.sort_by_key(|(type_index, _)| *type_index);
+        wanted.sort_by_key(|(type_index, _)| *type_index);
+        if current == wanted {
+            return;
+        }
+
+        for (type_index, _) in current {
+            lm.remove_overridden_type_index_cost(type_index);
+        }
+
+        for cost in &self.type_index_costs {
+            if !cost.is_valid() || !lm.override_type_index_cost(cost.type_index as usize, cost.cost)
+            {
+                log::error!(
+                    "[NavigationAgent#{}] {}",
+                    self.id,
+                    InvalidTypeIndexCost(*cost)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use bon::Builder;
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{
+    collisions::{RigidBody, RigidBodyId},
+    math::Vec3,
+    navigation::Character,
+    utils::WorldEntity,
+    world::{World, WorldId},
+};
+
+pub type NavigationCharacterId = u64;
+
+/// The speed above which a character following a rigid body is considered
+/// teleported rather than moving, its velocity is then zeroed.
+const MAX_SYNCED_SPEED: f32 = 50.0;
+
+#[table(accessor = steng_navigation_characters, public)]
+#[derive(Builder, Clone, Debug)]
+/// A character avoided by navigation agents, typically a player. Characters are
+/// not moved by the navigation system: their position is either set by the game
+/// or follows the rigid body they are linked to.
+pub struct NavigationCharacter {
+    #[primary_key]
+    #[auto_inc]
+    #[builder(default = 0)]
+    pub id: NavigationCharacterId,
+    #[index(btree)]
+    #[builder(default = 1)]
+    pub world_id: WorldId,
+    /// An optional external ID for the character. This can be used to link the
+    /// character to an entity in another system.
+    pub external_id: Option<u64>,
+    /// The rigid body the character follows. When set, the position and velocity
+    /// of the character are updated from the body every navigation tick.
+    pub rigid_body_id: Option<RigidBodyId>,
+    #[builder(default = Vec3::ZERO)]
+    pub position: Vec3,
+    #[builder(default = Vec3::ZERO)]
+    pub velocity: Vec3,
+    #[builder(default = 0.5)]
+    pub radius: f32,
+    /// The rigid body the position was last synced from, the velocity is only
+    /// derived from the distance travelled since a sync from the same body.
+    #[builder(skip)]
+    synced_rigid_body_id: Option<RigidBodyId>,
+}
+
+impl WorldEntity for NavigationCharacter {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_characters().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_characters().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_navigation_characters()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_navigation_characters()
+            .world_id()
+            .filter(world_id)
+            .map(|character| (character.id, character))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_navigation_characters()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_characters().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_navigation_characters().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_navigation_characters()
+            .world_id()
+            .filter(world_id)
+            .for_each(|character| {
+                ctx.db
+                    .steng_navigation_characters()
+                    .id()
+                    .delete(character.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_navigation_characters()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl From<&NavigationCharacter> for Character {
+    fn from(value: &NavigationCharacter) -> Self {
+        Character {
+            position: value.position,
+            velocity: value.velocity,
+            radius: value.radius,
+        }
+    }
+}
+
+/// Moves the characters linked to a rigid body to the body's position, deriving
+/// their velocity from the distance travelled since the last tick, and returns
+/// every character of the world. The velocity is zero on the first sync from a
+/// body and when the body moved faster than [`MAX_SYNCED_SPEED`], e.g. teleports.
+pub(crate) fn sync_navigation_characters(
+    ctx: &ReducerContext,
+    world: &World,
+    delta_time: f32,
+) -> Vec<Character> {
+    NavigationCharacter::iter(ctx, world.id)
+        .map(|mut character| {
+            if let Some(rigid_body) = character
+                .rigid_body_id
+                .and_then(|rigid_body_id| RigidBody::find(ctx, rigid_body_id))
+            {
+                let velocity =
+                    if delta_time > 0.0 && character.synced_rigid_body_id == Some(rigid_body.id) {
+                        (rigid_body.position - character.position) / delta_time
+                    } else {
+                        Vec3::ZERO
+                    };
+                let velocity = if velocity.length() > MAX_SYNCED_SPEED {
+                    Vec3::ZERO
+                } else {
+                    velocity
+                };
+                if rigid_body.position != character.position
+                    || velocity != character.velocity
+                    || character.synced_rigid_body_id != Some(rigid_body.id)
+                {
+                    character.position = rigid_body.position;
+                    character.velocity = velocity;
+                    character.synced_rigid_body_id = Some(rigid_body.id);
+                    character = character.update(ctx);
+                }
+            }
+
+            Character::from(&character)
+        })
+        .collect()
+}
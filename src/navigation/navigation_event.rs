@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{
+    navigation::{NavigationAgent, NavigationAgentId, NavigationState},
+    utils::WorldEntity,
+    world::WorldId,
+};
+
+pub type NavigationEventId = u64;
+
+#[table(accessor = steng_navigation_events, public)]
+#[derive(Clone, Debug)]
+/// A state transition of an agent during the last navigation tick, e.g. reaching
+/// its destination or failing to find a path. Events only live for one tick:
+/// they are cleared at the start of the next navigation tick of the world, so
+/// game code, behavior trees and spells running in the same tick can react to
+/// them without diffing agent states themselves.
+pub struct NavigationEvent {
+    #[primary_key]
+    #[auto_inc]
+    pub id: NavigationEventId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    #[index(btree)]
+    pub agent_id: NavigationAgentId,
+    pub previous_state: NavigationState,
+    pub new_state: NavigationState,
+    pub timestamp_ms: i64,
+}
+
+impl NavigationEvent {
+    /// Returns the events of the given agent during the last navigation tick.
+    pub fn for_agent(
+        ctx: &ReducerContext,
+        agent_id: NavigationAgentId,
+    ) -> impl Iterator<Item = Self> {
+        ctx.db.steng_navigation_events().agent_id().filter(agent_id)
+    }
+}
+
+impl WorldEntity for NavigationEvent {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_events().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_events().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db.steng_navigation_events().world_id().filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_navigation_events()
+            .world_id()
+            .filter(world_id)
+            .map(|event| (event.id, event))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_navigation_events()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_events().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_navigation_events().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_navigation_events()
+            .world_id()
+            .filter(world_id)
+            .for_each(|event| {
+                ctx.db.steng_navigation_events().id().delete(event.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_navigation_events()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+/// Records the transition of an agent to a new state, if its state changed.
+pub(crate) fn record_state_transition(
+    ctx: &ReducerContext,
+    agent: &NavigationAgent,
+    new_state: NavigationState,
+    now: i64,
+) {
+    let previous_state = agent.state();
+    if previous_state == new_state {
+        return;
+    }
+
+    NavigationEvent {
+        id: 0,
+        world_id: agent.world_id(),
+        agent_id: agent.id(),
+        previous_state,
+        new_state,
+        timestamp_ms: now,
+    }
+    .insert(ctx);
+}
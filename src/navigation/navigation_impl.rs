@@ -1,17 +1,22 @@
-use std::{collections::HashMap, sync::Arc};
+use std::collections::{HashMap, HashSet};
 
-use landmass::{
-    Archipelago as LmArchipelago, ArchipelagoOptions, Character as LmCharacter, Island,
-    PointSampleDistance3d, Transform, ValidNavigationMesh,
-};
+use landmass::{AgentId, Archipelago as LmArchipelago};
 use spacetimedb::ReducerContext;
 
 use crate::{
-    math::Vec3,
+    collisions::{Collider, RigidBody, RigidBodyId, shape_wrapper::ShapeWrapper},
+    math::{Quat, Vec3},
     navigation::{
-        NavigationAgent, NavigationAgentId, coordinates::XYZ, validated_navmesh::NavMesh,
+        ForcedMovement, NavigationAgent, NavigationAgentId, NavigationEvent, NavigationSettings,
+        NavigationState, ReachedAnimationLink,
+        archipelago_cache::{CachedArchipelago, with_archipelago},
+        coordinates::XYZ,
+        navigation_character::sync_navigation_characters,
+        navigation_event::record_state_transition,
+        navigation_path::sync_navigation_paths,
+        queries::raycast_archipelago,
     },
-    utils::{LogStopwatch, WorldEntity},
+    utils::{LogStopwatch, WorldEntity, now_ms},
     world::World,
 };
 
@@ -126,83 +131,222 @@ pub(crate) fn tick_navigation(
         world.debug_navigation,
     );
 
-    sw.span("build_archipelago");
-    let radius = 0.5;
-    // TODO: Curently recreating the archipelago every tick makes computed paths invalid,
-    // causing agents to recompute paths every tick.
-    // We need to persist the archipelago between ticks.
-    let mut archipelago = Archipelago::new(ArchipelagoOptions {
-        point_sample_distance: PointSampleDistance3d {
-            horizontal_distance: radius,
-            distance_above: radius * 2.0,
-            distance_below: radius * 2.0,
-            vertical_preference_ratio: 2.0,
-            animation_link_max_vertical_distance: 0.5 * radius,
-        },
-        neighbourhood: 10.0 * radius,
-        avoidance_time_horizon: 0.5,
-        obstacle_avoidance_time_horizon: 0.25,
-        reached_destination_avoidance_responsibility: 0.1,
-    });
+    sw.span("clear_navigation_events");
+    NavigationEvent::clear(ctx, world.id);
 
-    for navmesh in NavMesh::iter(ctx, world.id) {
-        let translation = navmesh.translation;
-        let rotation = navmesh.rotation;
+    let settings = NavigationSettings::for_world(ctx, world.id);
+    with_archipelago(&settings, |cache| {
+        sw.span("sync_islands");
+        cache.sync_islands(ctx, world.id);
+        cache.sync_node_types(ctx, world.id);
+        cache.sync_animation_links(ctx, world.id);
 
-        let navmesh: ValidNavigationMesh<XYZ> =
-            bincode::serde::decode_from_slice(&navmesh.data, bincode::config::standard())
-                .expect("Failed to decode navmesh")
-                .0;
-        let navmesh: Arc<ValidNavigationMesh<XYZ>> = Arc::new(navmesh);
+        sw.span("sync_agents");
+        let agents = cache.sync_agents(NavigationAgent::iter(ctx, world.id));
+        cache.sync_characters(
+            characters
+                .chain(sync_navigation_characters(ctx, world, delta_time))
+                .chain(rigid_body_obstacles(ctx, world, agents.values())),
+        );
 
-        archipelago.add_island(Island::new(
-            Transform {
-                translation,
-                rotation,
-            },
-            navmesh,
-        ));
-    }
+        let agents = update_agents(ctx, world, &mut sw, cache, agents, delta_time);
 
-    sw.span("create_agents");
-    let mut agents = HashMap::new();
+        sw.span("sync_navigation_paths");
+        sync_navigation_paths(ctx, world, &settings, cache, &agents);
 
-    for eng_agent in NavigationAgent::iter(ctx, world.id) {
-        let agent_id = archipelago.add_agent((&eng_agent).into());
-        agents.insert(agent_id, eng_agent);
-    }
+        sw.span("sync_bound_rigid_bodies");
+        sync_bound_rigid_bodies(ctx, &agents);
 
-    for character in characters {
-        archipelago.add_character(LmCharacter {
-            position: character.position,
-            velocity: character.velocity,
-            radius: character.radius,
-        });
-    }
+        agents
+    })
+}
 
+/// Updates the archipelago and writes the resulting velocities, positions and
+/// states back to the agents.
+fn update_agents(
+    ctx: &ReducerContext,
+    world: &World,
+    sw: &mut LogStopwatch,
+    cache: &mut CachedArchipelago,
+    agents: HashMap<AgentId, NavigationAgent>,
+    delta_time: f32,
+) -> HashMap<NavigationAgentId, NavigationAgent> {
     sw.span("update_archipelago");
-    archipelago.update(&mut ctx.rng(), delta_time);
+    cache.archipelago.update(&mut ctx.rng(), delta_time);
+    let archipelago = &cache.archipelago;
 
     sw.span("update_agents");
     let mut updated_agents = HashMap::new();
+    let mut forced_movements: HashMap<NavigationAgentId, ForcedMovement> =
+        ForcedMovement::iter(ctx, world.id)
+            .map(|movement| (movement.agent_id, movement))
+            .collect();
+    let now = now_ms(ctx);
 
     for (lm_agent_id, mut eng_agent) in agents {
+        if let Some(mut movement) = forced_movements.remove(&eng_agent.id()) {
+            let pending_event = movement.combat_event.is_some();
+            let on_mesh =
+                apply_forced_movement(ctx, world, archipelago, &mut eng_agent, &mut movement, now);
+            if !on_mesh || movement.is_finished(now) {
+                movement.delete(ctx);
+            } else if pending_event && movement.combat_event.is_none() {
+                movement.update(ctx);
+            }
+
+            let navagent = eng_agent.update(ctx);
+            updated_agents.insert(navagent.id(), navagent);
+            continue;
+        }
+
         let lm_agent = archipelago.get_agent(lm_agent_id).unwrap();
-        let velocity = lm_agent.get_desired_velocity();
-
-        eng_agent.set_velocity(*velocity);
-        let new_pos = eng_agent.position() + velocity * delta_time;
-        if let Ok(point) = archipelago.sample_point(
-            new_pos,
-            &archipelago.archipelago_options.point_sample_distance,
-        ) {
-            eng_agent.set_position(point.point());
+        let state: NavigationState = lm_agent.state().into();
+
+        // Agents using an animation link are moved by the game until it finishes the link.
+        if state != NavigationState::UsingAnimationLink {
+            let velocity = lm_agent.get_desired_velocity();
+
+            eng_agent.set_velocity(*velocity);
+            let new_pos = eng_agent.position() + velocity * delta_time;
+            if let Ok(point) = archipelago.sample_point(
+                new_pos,
+                &archipelago.archipelago_options.point_sample_distance,
+            ) {
+                eng_agent.set_position(point.point());
+            }
         }
 
-        eng_agent.set_state(lm_agent.state().into());
+        match state {
+            NavigationState::ReachedAnimationLink => {
+                let reached = lm_agent.reached_animation_link().and_then(|link| {
+                    Some(ReachedAnimationLink {
+                        link_id: cache.animation_link_id(link.link_id)?,
+                        start_point: link.start_point,
+                        end_point: link.end_point,
+                    })
+                });
+                eng_agent.set_reached_animation_link(reached);
+            }
+            NavigationState::UsingAnimationLink => {}
+            _ => {
+                eng_agent.set_reached_animation_link(None);
+            }
+        }
+
+        record_state_transition(ctx, &eng_agent, state, now);
+        eng_agent.set_state(state);
         let navagent = eng_agent.update(ctx);
         updated_agents.insert(navagent.id(), navagent);
     }
 
+    // Movements of agents that no longer exist.
+    for movement in forced_movements.into_values() {
+        movement.delete(ctx);
+    }
+
     updated_agents
 }
+
+/// Returns the rigid bodies flagged as navigation obstacles as characters, using
+/// the bounding circle of their collider's horizontal footprint. Bodies bound to
+/// one of the agents are skipped so that agents do not avoid themselves.
+fn rigid_body_obstacles<'a>(
+    ctx: &ReducerContext,
+    world: &World,
+    agents: impl Iterator<Item = &'a NavigationAgent>,
+) -> Vec<Character> {
+    let bound: HashSet<RigidBodyId> = agents.filter_map(|agent| agent.rigid_body_id).collect();
+    RigidBody::iter(ctx, world.id)
+        .filter(|rigid_body| rigid_body.navigation_obstacle && !bound.contains(&rigid_body.id))
+        .filter_map(|rigid_body| {
+            let collider = Collider::find(ctx, rigid_body.collider_id)?;
+            let aabb = ShapeWrapper::from(&collider).collision_aabb(&(&rigid_body).into(), 0.0);
+            let (mins, maxs): (Vec3, Vec3) = (aabb.mins.into(), aabb.maxs.into());
+            let half_extents = (maxs - mins) * 0.5;
+
+            Some(Character {
+                position: Vec3::new((mins.x + maxs.x) * 0.5, mins.y, (mins.z + maxs.z) * 0.5),
+                velocity: Vec3::ZERO,
+                radius: half_extents.x.hypot(half_extents.z),
+            })
+        })
+        .collect()
+}
+
+/// Moves the agent along its forced movement, keeping it on the navigation mesh.
+/// Movements over a duration walk the mesh from the agent's position and stop
+/// at the first point off the mesh, so they cannot cross walls or gaps. Instant
+/// movements (teleports) only need their destination to be on a mesh. The agent
+/// moves at the speed of the movement, teleported agents have no velocity. If
+/// the agent cannot move any further, it stays where it stopped and false is
+/// returned. The combat event of the movement, if any, is recorded and taken
+/// from the movement the first time the agent actually moves.
+fn apply_forced_movement(
+    ctx: &ReducerContext,
+    world: &World,
+    archipelago: &Archipelago,
+    agent: &mut NavigationAgent,
+    movement: &mut ForcedMovement,
+    now: i64,
+) -> bool {
+    let target = movement.position_at(now);
+    let sample_distance = &archipelago.archipelago_options.point_sample_distance;
+    let (position, on_mesh) = if movement.ends_at_ms == movement.started_at_ms {
+        match archipelago.sample_point(target, sample_distance) {
+            Ok(point) => (point.point(), true),
+            Err(_) => (agent.position(), false),
+        }
+    } else {
+        match raycast_archipelago(archipelago, agent.position(), target) {
+            Some(hit) => (hit.point, false),
+            None => match archipelago.sample_point(target, sample_distance) {
+                Ok(point) => (point.point(), true),
+                Err(_) => (agent.position(), false),
+            },
+        }
+    };
+
+    agent.set_velocity(if on_mesh {
+        movement.velocity()
+    } else {
+        Vec3::ZERO
+    });
+    if position != agent.position()
+        && let Some(event) = movement.combat_event.take()
+    {
+        event.record(ctx, world.id);
+    }
+    agent.set_position(position);
+    on_mesh
+}
+
+/// Moves the rigid bodies bound to an agent (through its `rigid_body_id`) to the
+/// agent's position, facing its velocity. Bodies of agents standing still keep
+/// their rotation.
+fn sync_bound_rigid_bodies(
+    ctx: &ReducerContext,
+    agents: &HashMap<NavigationAgentId, NavigationAgent>,
+) {
+    for agent in agents.values() {
+        let Some(mut rigid_body) = agent
+            .rigid_body_id
+            .and_then(|rigid_body_id| RigidBody::find(ctx, rigid_body_id))
+        else {
+            continue;
+        };
+
+        let velocity = agent.velocity();
+        let rotation = if velocity.x != 0.0 || velocity.z != 0.0 {
+            Quat::facing(velocity)
+        } else {
+            rigid_body.rotation
+        };
+        if rigid_body.position == agent.position() && rigid_body.rotation == rotation {
+            continue;
+        }
+
+        rigid_body.position = agent.position();
+        rigid_body.rotation = rotation;
+        rigid_body.update(ctx);
+    }
+}
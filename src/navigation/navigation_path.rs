@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{
+    math::Vec3,
+    navigation::{
+        NavigationAgent, NavigationAgentId, NavigationSettings, NavigationState, TypeIndexCost,
+        archipelago_cache::CachedArchipelago, find_path::compute_path,
+    },
+    utils::WorldEntity,
+    world::{World, WorldId},
+};
+
+pub type NavigationPathId = u64;
+
+#[table(accessor = steng_navigation_paths, public)]
+#[derive(Clone, Debug)]
+/// The remaining path of an agent towards its destination, exposed for client-side
+/// prediction and debug drawing. Paths are only maintained for agents with
+/// [`NavigationAgent::expose_path`] set, or for every agent of worlds with
+/// [`NavigationSettings::expose_paths`] set.
+///
+/// The path is an estimate: it is computed separately with
+/// [`crate::navigation::find_path`] from the agent's position, destination and node
+/// type costs, as the path landmass plans for the agent is not exposed. Both
+/// usually match, but the agent may take a different route, e.g. when several
+/// routes cost the same or when it replans on its own. Rows are updated only when
+/// the path changes:
+/// when the agent goes past its next waypoint, or when the path is computed again
+/// because the agent got a new destination or node type costs, the navigation
+/// meshes, animation links or node types of the world changed, or the agent left
+/// the path (e.g. avoiding other agents or being knocked back).
+pub struct NavigationPath {
+    #[primary_key]
+    #[auto_inc]
+    pub id: NavigationPathId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    /// The agent following the path.
+    #[unique]
+    pub agent_id: NavigationAgentId,
+    /// The destination the path leads to.
+    pub destination: Vec3,
+    /// The last corner the agent went past, or the point the path was computed
+    /// from.
+    pub previous_corner: Vec3,
+    /// The remaining corners of the path, starting with the next waypoint and
+    /// ending with the destination snapped onto the navigation mesh.
+    pub corners: Vec<Vec3>,
+}
+
+impl NavigationPath {
+    /// The point the agent is currently moving towards.
+    pub fn next_waypoint(&self) -> Option<Vec3> {
+        self.corners.first().copied()
+    }
+
+    /// Returns the path of the given agent, if exposed.
+    pub fn find_by_agent(ctx: &ReducerContext, agent_id: NavigationAgentId) -> Option<Self> {
+        ctx.db.steng_navigation_paths().agent_id().find(agent_id)
+    }
+}
+
+impl WorldEntity for NavigationPath {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_paths().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_paths().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db.steng_navigation_paths().world_id().filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_navigation_paths()
+            .world_id()
+            .filter(world_id)
+            .map(|path| (path.id, path))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_navigation_paths()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_paths().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_navigation_paths().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        for path in ctx.db.steng_navigation_paths().world_id().filter(world_id) {
+            ctx.db.steng_navigation_paths().id().delete(path.id);
+        }
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_navigation_paths()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+/// How far from its path, in agent radii, an agent can be before its path is
+/// computed again.
+const CORRIDOR_RADII: f32 = 2.0;
+
+/// What the exposed path of an agent was computed from. The path is computed
+/// again when any of it changes.
+#[derive(Clone, Debug)]
+pub(crate) struct PathSource {
+    destination: Vec3,
+    type_index_costs: Vec<TypeIndexCost>,
+    /// The [`CachedArchipelago::generation`] the path was computed in.
+    generation: u64,
+    /// Whether a path was found. Failures are not retried until the source
+    /// changes.
+    found: bool,
+}
+
+impl PathSource {
+    /// Returns true if both paths are computed from the same request, whether
+    /// they were found or not.
+    fn same_request(&self, other: &PathSource) -> bool {
+        self.destination == other.destination
+            && self.type_index_costs == other.type_index_costs
+            && self.generation == other.generation
+    }
+}
+
+/// How an agent moved along its exposed path.
+enum Progress {
+    /// The agent is still heading to its next waypoint.
+    Unchanged,
+    /// The agent went past some corners, they were dropped.
+    Advanced,
+    /// The agent is too far from the path to follow it.
+    OffPath,
+}
+
+/// Updates the exposed paths of the agents after they moved, see
+/// [`NavigationPath`].
+pub(crate) fn sync_navigation_paths(
+    ctx: &ReducerContext,
+    world: &World,
+    settings: &NavigationSettings,
+    cache: &mut CachedArchipelago,
+    agents: &HashMap<NavigationAgentId, NavigationAgent>,
+) {
+    let mut paths: HashMap<NavigationAgentId, NavigationPath> = NavigationPath::iter(ctx, world.id)
+        .map(|path| (path.agent_id, path))
+        .collect();
+
+    for agent in agents.values() {
+        let path = paths.remove(&agent.id());
+        let destination = agent
+            .destination()
+            .filter(|_| settings.expose_paths || agent.expose_path())
+            .filter(|_| has_path(agent.state()));
+        let Some(destination) = destination else {
+            if let Some(path) = path {
+                path.delete(ctx);
+            }
+            cache.path_sources.remove(&agent.id());
+            continue;
+        };
+
+        let mut source = PathSource {
+            destination,
+            type_index_costs: agent.type_index_costs().to_vec(),
+            generation: cache.generation,
+            found: true,
+        };
+        let cached = cache
+            .path_sources
+            .get(&agent.id())
+            .filter(|cached| cached.same_request(&source));
+
+        let path = match (path, cached) {
+            // The path could not be computed from this source, do not retry.
+            (None, Some(cached)) if !cached.found => continue,
+            (Some(mut path), Some(_)) => match advance(&mut path, agent) {
+                Progress::Unchanged => continue,
+                Progress::Advanced => {
+                    path.update(ctx);
+                    continue;
+                }
+                Progress::OffPath => Some(path),
+            },
+            (path, _) => path,
+        };
+
+        let type_index_costs = source
+            .type_index_costs
+            .iter()
+            .filter(|cost| cost.is_valid())
+            .map(|cost| (cost.type_index as usize, cost.cost))
+            .collect();
+        let corners = compute_path(
+            &cache.archipelago,
+            agent.position(),
+            destination,
+            &type_index_costs,
+        )
+        .map(|nav_path| nav_path.corners);
+        source.found = corners.is_ok();
+        cache.path_sources.insert(agent.id(), source);
+
+        match (path, corners) {
+            (Some(mut path), Ok(mut corners)) => {
+                path.destination = destination;
+                path.previous_corner = corners.remove(0);
+                path.corners = corners;
+                path.update(ctx);
+            }
+            (None, Ok(mut corners)) => {
+                NavigationPath {
+                    id: 0,
+                    world_id: world.id,
+                    agent_id: agent.id(),
+                    destination,
+                    previous_corner: corners.remove(0),
+                    corners,
+                }
+                .insert(ctx);
+            }
+            (Some(path), Err(_)) => path.delete(ctx),
+            (None, Err(_)) => {}
+        }
+    }
+
+    // Paths of agents that no longer exist.
+    for path in paths.into_values() {
+        path.delete(ctx);
+    }
+    cache
+        .path_sources
+        .retain(|agent_id, _| agents.contains_key(agent_id));
+}
+
+/// Drops the corners the agent went past. The agent is matched to the closest
+/// segment of its path, landmass cutting corners when it can see past them, and
+/// the corners before that segment are dropped. The next waypoint is also
+/// dropped once the agent is within its radius, unless it is the destination.
+fn advance(path: &mut NavigationPath, agent: &NavigationAgent) -> Progress {
+    let position = agent.position();
+    let mut closest = None;
+    let mut start = path.previous_corner;
+    for (index, end) in path.corners.iter().enumerate() {
+        let distance = horizontal_distance_to_segment(position, start, *end);
+        // Later segments win ties, the agent being at the corner joining them.
+        if closest.is_none_or(|(_, closest_distance)| distance <= closest_distance) {
+            closest = Some((index, distance));
+        }
+        start = *end;
+    }
+
+    let Some((mut reached, distance)) = closest else {
+        return Progress::Unchanged;
+    };
+    if distance > agent.radius() * CORRIDOR_RADII {
+        return Progress::OffPath;
+    }
+
+    if reached + 1 < path.corners.len() {
+        let offset = path.corners[reached] - position;
+        if offset.x.hypot(offset.z) <= agent.radius() {
+            reached += 1;
+        }
+    }
+    if reached == 0 {
+        return Progress::Unchanged;
+    }
+
+    path.previous_corner = path.corners[reached - 1];
+    path.corners.drain(..reached);
+    Progress::Advanced
+}
+
+/// The distance from `point` to the segment from `start` to `end`, ignoring
+/// heights.
+fn horizontal_distance_to_segment(point: Vec3, start: Vec3, end: Vec3) -> f32 {
+    let (segment_x, segment_z) = (end.x - start.x, end.z - start.z);
+    let (point_x, point_z) = (point.x - start.x, point.z - start.z);
+    let length_squared = segment_x * segment_x + segment_z * segment_z;
+    let t = if length_squared > 0.0 {
+        ((point_x * segment_x + point_z * segment_z) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (point_x - segment_x * t).hypot(point_z - segment_z * t)
+}
+
+/// Whether an agent in the given state is following a path.
+fn has_path(state: NavigationState) -> bool {
+    matches!(
+        state,
+        NavigationState::Moving
+            | NavigationState::ReachedAnimationLink
+            | NavigationState::UsingAnimationLink
+            | NavigationState::Paused
+    )
+}
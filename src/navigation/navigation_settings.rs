@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use bon::Builder;
+use landmass::{ArchipelagoOptions, PointSampleDistance3d};
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{navigation::coordinates::XYZ, utils::WorldEntity, world::WorldId};
+
+pub type NavigationSettingsId = u64;
+
+#[table(accessor = steng_navigation_settings)]
+#[derive(Builder, Clone, Copy, Debug)]
+/// The options of the navigation system of a world. Worlds without settings use
+/// the defaults, tuned for agents with a radius of about 0.5.
+pub struct NavigationSettings {
+    #[primary_key]
+    #[auto_inc]
+    #[builder(default = 0)]
+    pub id: NavigationSettingsId,
+    #[unique]
+    #[builder(default = 1)]
+    pub world_id: WorldId,
+
+    /// The horizontal distance within which a point can be sampled on the
+    /// navigation mesh.
+    #[builder(default = 0.5)]
+    pub point_sample_horizontal_distance: f32,
+    /// The distance above a point within which it can be sampled on the
+    /// navigation mesh.
+    #[builder(default = 1.0)]
+    pub point_sample_distance_above: f32,
+    /// The distance below a point within which it can be sampled on the
+    /// navigation mesh.
+    #[builder(default = 1.0)]
+    pub point_sample_distance_below: f32,
+    /// How much vertical distance is penalized over horizontal distance when
+    /// picking the closest point on the navigation mesh.
+    #[builder(default = 2.0)]
+    pub point_sample_vertical_preference_ratio: f32,
+    /// The maximum vertical distance between an agent and the start of an
+    /// animation link for the agent to use it.
+    #[builder(default = 0.25)]
+    pub animation_link_max_vertical_distance: f32,
+    /// The distance that an agent will consider avoiding another agent.
+    #[builder(default = 5.0)]
+    pub neighbourhood: f32,
+    /// The time into the future that collisions with other agents should be
+    /// avoided.
+    #[builder(default = 0.5)]
+    pub avoidance_time_horizon: f32,
+    /// The time into the future that collisions with obstacles should be avoided.
+    #[builder(default = 0.25)]
+    pub obstacle_avoidance_time_horizon: f32,
+    /// The avoidance responsibility to use when an agent has reached its target,
+    /// between 0.0 and 1.0. A lower value lets moving agents push through it.
+    #[builder(default = 0.1)]
+    pub reached_destination_avoidance_responsibility: f32,
+    /// Whether the paths of every agent are exposed in the
+    /// [`crate::navigation::NavigationPath`] table, not only the ones of agents
+    /// with [`crate::navigation::NavigationAgent::expose_path`] set.
+    #[builder(default = false)]
+    pub expose_paths: bool,
+}
+
+impl WorldEntity for NavigationSettings {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_settings().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_settings().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_navigation_settings()
+            .world_id()
+            .find(world_id)
+            .into_iter()
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        Self::iter(ctx, world_id)
+            .map(|settings| (settings.id, settings))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        Self::iter(ctx, world_id).collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_settings().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_navigation_settings().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_navigation_settings()
+            .world_id()
+            .delete(world_id);
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        Self::iter(ctx, world_id).count()
+    }
+}
+
+impl NavigationSettings {
+    /// Returns the settings of the world, or the defaults if it has none.
+    pub fn for_world(ctx: &ReducerContext, world_id: WorldId) -> Self {
+        ctx.db
+            .steng_navigation_settings()
+            .world_id()
+            .find(world_id)
+            .unwrap_or_else(|| NavigationSettings::builder().world_id(world_id).build())
+    }
+
+    /// Derives the settings from the radius of the agents of the world, the way
+    /// the defaults are derived from a radius of 0.5.
+    pub fn from_agent_radius(world_id: WorldId, radius: f32) -> Self {
+        NavigationSettings::builder()
+            .world_id(world_id)
+            .point_sample_horizontal_distance(radius)
+            .point_sample_distance_above(radius * 2.0)
+            .point_sample_distance_below(radius * 2.0)
+            .animation_link_max_vertical_distance(radius * 0.5)
+            .neighbourhood(radius * 10.0)
+            .build()
+    }
+}
+
+impl From<&NavigationSettings> for ArchipelagoOptions<XYZ> {
+    fn from(value: &NavigationSettings) -> Self {
+        ArchipelagoOptions {
+            point_sample_distance: PointSampleDistance3d {
+                horizontal_distance: value.point_sample_horizontal_distance,
+                distance_above: value.point_sample_distance_above,
+                distance_below: value.point_sample_distance_below,
+                vertical_preference_ratio: value.point_sample_vertical_preference_ratio,
+                animation_link_max_vertical_distance: value.animation_link_max_vertical_distance,
+            },
+            neighbourhood: value.neighbourhood,
+            avoidance_time_horizon: value.avoidance_time_horizon,
+            obstacle_avoidance_time_horizon: value.obstacle_avoidance_time_horizon,
+            reached_destination_avoidance_responsibility: value
+                .reached_destination_avoidance_responsibility,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bon::Builder;
+use spacetimedb::{ReducerContext, SpacetimeType, Table, table};
+
+use crate::{utils::WorldEntity, world::WorldId};
+
+pub type NavNodeTypeId = u64;
+
+/// The cost of traversing the polygons of a type index, relative to the default
+/// cost of 1.0. Must be greater than 0.0.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub struct TypeIndexCost {
+    pub type_index: u32,
+    pub cost: f32,
+}
+
+impl TypeIndexCost {
+    /// Returns true if the cost can be used by landmass, i.e. is greater than 0.0.
+    pub fn is_valid(&self) -> bool {
+        self.cost > 0.0
+    }
+}
+
+/// A type index cost that is not greater than 0.0, or is NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InvalidTypeIndexCost(pub TypeIndexCost);
+
+impl Display for InvalidTypeIndexCost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid cost {} for type index {}",
+            self.0.cost, self.0.type_index
+        )
+    }
+}
+
+#[table(accessor = steng_navigation_node_types)]
+#[derive(Builder, Clone, Debug)]
+/// A type of terrain of the navigation meshes of a world, e.g. roads or swamps.
+/// Polygons are tagged with a type index through
+/// [`crate::navigation::ExternalNavMesh::polygon_type_indices`], agents prefer
+/// the polygons whose type is cheaper to traverse.
+pub struct NavNodeType {
+    #[primary_key]
+    #[auto_inc]
+    #[builder(default = 0)]
+    pub id: NavNodeTypeId,
+    #[index(btree)]
+    #[builder(default = 1)]
+    pub world_id: WorldId,
+    /// The type index of the polygons of this type.
+    pub type_index: u32,
+    pub name: String,
+    /// The cost of traversing this type, relative to the default cost of 1.0.
+    /// Must be greater than 0.0.
+    #[builder(default = 1.0)]
+    pub cost: f32,
+}
+
+impl WorldEntity for NavNodeType {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_node_types().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_navigation_node_types().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_navigation_node_types()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_navigation_node_types()
+            .world_id()
+            .filter(world_id)
+            .map(|node_type| (node_type.id, node_type))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_navigation_node_types()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_navigation_node_types().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_navigation_node_types().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_navigation_node_types()
+            .world_id()
+            .filter(world_id)
+            .for_each(|node_type| {
+                ctx.db
+                    .steng_navigation_node_types()
+                    .id()
+                    .delete(node_type.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_navigation_node_types()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
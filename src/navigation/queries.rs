@@ -0,0 +1,178 @@
+use landmass::PointSampleDistance3d;
+use spacetimedb::ReducerContext;
+
+use crate::{
+    math::Vec3,
+    navigation::{Archipelago, NavigationSettings, archipelago_cache::with_archipelago},
+    world::WorldId,
+};
+
+/// The horizontal distance within which raycast probes are sampled. Probes are
+/// not snapped back onto the meshes, so edges and holes are detected however
+/// close they are.
+const PROBE_HORIZONTAL_DISTANCE: f32 = 1e-3;
+
+/// The maximum number of probes walking a raycast, longer lines are probed in
+/// larger steps.
+const MAX_RAYCAST_PROBES: usize = 1024;
+
+/// The number of bisection steps refining where a raycast leaves the meshes.
+const EXIT_BISECTION_STEPS: u32 = 12;
+
+/// How far from a point the navigation meshes are searched when sampling it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleDistance {
+    /// The horizontal distance within which a point can be sampled.
+    pub horizontal_distance: f32,
+    /// The distance above the point within which it can be sampled.
+    pub distance_above: f32,
+    /// The distance below the point within which it can be sampled.
+    pub distance_below: f32,
+}
+
+/// A point snapped onto a navigation mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampledNavPoint {
+    /// The point on the navigation mesh.
+    pub point: Vec3,
+    /// The ID of the [`crate::navigation::NavMesh`] the point is on.
+    pub navmesh_id: u64,
+}
+
+/// Where a navigation mesh raycast left the walkable area.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NavRaycastHit {
+    /// The last walkable point along the ray.
+    pub point: Vec3,
+    /// The horizontal distance from the start of the ray to `point`.
+    pub distance: f32,
+}
+
+/// Snaps a point onto the navigation meshes of the world, e.g. to turn a click
+/// into a destination. If `distance` is `None`, the world's
+/// [`NavigationSettings`] are used. Returns `None` if no mesh is close enough.
+pub fn sample_point(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    point: Vec3,
+    distance: Option<SampleDistance>,
+) -> Option<SampledNavPoint> {
+    let settings = NavigationSettings::for_world(ctx, world_id);
+    with_archipelago(&settings, |cache| {
+        cache.sync_islands(ctx, world_id);
+
+        let mut sample_distance = cache
+            .archipelago
+            .archipelago_options
+            .point_sample_distance
+            .clone();
+        if let Some(distance) = distance {
+            sample_distance.horizontal_distance = distance.horizontal_distance;
+            sample_distance.distance_above = distance.distance_above;
+            sample_distance.distance_below = distance.distance_below;
+        }
+
+        let sampled = cache
+            .archipelago
+            .sample_point(point, &sample_distance)
+            .ok()?;
+        Some(SampledNavPoint {
+            point: sampled.point(),
+            navmesh_id: cache.navmesh_id(sampled.island())?,
+        })
+    })
+}
+
+/// Walks the navigation meshes of the world in a straight line from `from` to
+/// `to`, e.g. to check a walkable line of sight. Returns `None` if the whole line
+/// is walkable, or the last walkable point before the line leaves the meshes.
+///
+/// `from` is snapped onto the meshes using the world's [`NavigationSettings`].
+/// The line is then probed from the snapped point in steps of half the world's
+/// horizontal sample distance (larger for very long lines, which are probed at
+/// most a fixed number of times) without any horizontal snapping, so gaps
+/// narrower than a step can be missed. A step climbing or dropping more than the sample distance above or
+/// below counts as leaving the mesh.
+pub fn raycast(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    from: Vec3,
+    to: Vec3,
+) -> Option<NavRaycastHit> {
+    let settings = NavigationSettings::for_world(ctx, world_id);
+    with_archipelago(&settings, |cache| {
+        cache.sync_islands(ctx, world_id);
+        raycast_archipelago(&cache.archipelago, from, to)
+    })
+}
+
+/// Walks an already synced archipelago from `from` to `to`, see [`raycast`].
+pub(crate) fn raycast_archipelago(
+    archipelago: &Archipelago,
+    from: Vec3,
+    to: Vec3,
+) -> Option<NavRaycastHit> {
+    let sample_distance = &archipelago.archipelago_options.point_sample_distance;
+    let Ok(start) = archipelago.sample_point(from, sample_distance) else {
+        return Some(NavRaycastHit {
+            point: from,
+            distance: 0.0,
+        });
+    };
+
+    let origin = start.point();
+    let mut delta = to - origin;
+    delta.y = 0.0;
+    let length = delta.length();
+    let probe_distance = PointSampleDistance3d {
+        horizontal_distance: PROBE_HORIZONTAL_DISTANCE,
+        ..sample_distance.clone()
+    };
+    // Probing at the height of the last point rejects steps climbing or dropping
+    // further than the sample distance.
+    let probe = |distance: f32, height: f32| {
+        let mut point = origin + delta * (distance / length);
+        point.y = height;
+        archipelago
+            .sample_point(point, &probe_distance)
+            .ok()
+            .map(|sampled| sampled.point())
+    };
+
+    let step = (sample_distance.horizontal_distance * 0.5)
+        .max(length / MAX_RAYCAST_PROBES as f32)
+        .max(f32::EPSILON);
+    let steps = ((length / step).ceil() as usize).min(MAX_RAYCAST_PROBES);
+
+    let mut last = origin;
+    let mut walked = 0.0;
+    for i in 1..=steps {
+        let distance = (i as f32 * step).min(length);
+        if let Some(next) = probe(distance, last.y) {
+            last = next;
+            walked = distance;
+            continue;
+        }
+
+        // The line leaves the meshes between the last two probes, bisect to find
+        // the exit point.
+        let mut off_mesh = distance;
+        for _ in 0..EXIT_BISECTION_STEPS {
+            let middle = (walked + off_mesh) * 0.5;
+            match probe(middle, last.y) {
+                Some(next) => {
+                    last = next;
+                    walked = middle;
+                }
+                None => off_mesh = middle,
+            }
+        }
+
+        return Some(NavRaycastHit {
+            point: last,
+            distance: walked,
+        });
+    }
+
+    None
+}
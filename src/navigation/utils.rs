@@ -1,30 +1,36 @@
 use crate::{
-    navigation::{ExternalNavMesh, coordinates::XYZ, validated_navmesh::NavMesh},
+    navigation::{
+        ExternalNavMesh,
+        coordinates::XYZ,
+        validated_navmesh::{NavMesh, NavMeshError},
+    },
     utils::WorldEntity,
     world::WorldId,
 };
 use landmass::NavigationMesh;
 use spacetimedb::ReducerContext;
 
+/// Validates the navigation mesh and stores it in the world.
 pub fn import_external_navmesh(
     ctx: &ReducerContext,
     world_id: WorldId,
     exteranal_navmesh: ExternalNavMesh,
-) {
+) -> Result<NavMesh, NavMeshError> {
     let translation = exteranal_navmesh.translation;
     let rotation = exteranal_navmesh.rotation;
 
-    let lm_nav_mesh: NavigationMesh<XYZ> = exteranal_navmesh.into();
-    let validated_navmesh = lm_nav_mesh.validate().expect("Failed to validate navmesh");
+    let lm_nav_mesh: NavigationMesh<XYZ> = exteranal_navmesh.try_into()?;
+    let validated_navmesh = lm_nav_mesh.validate().map_err(NavMeshError::Validation)?;
 
     let data = bincode::serde::encode_to_vec(validated_navmesh, bincode::config::standard())
-        .expect("Failed to encode navmesh");
-    NavMesh {
+        .map_err(|err| NavMeshError::Encode(err.to_string()))?;
+    Ok(NavMesh {
         id: 0,
         world_id,
         translation,
         rotation,
         data,
+        revision: 0,
     }
-    .insert(ctx);
+    .insert(ctx))
 }
@@ -1,9 +1,36 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display};
 
-use crate::{math::Vec3, utils::WorldEntity, world::WorldId};
+use crate::{math::Vec3, navigation::coordinates::XYZ, utils::WorldEntity, world::WorldId};
 
+use landmass::{ValidNavigationMesh, ValidationError};
 use spacetimedb::{ReducerContext, Table, table};
 
+/// The reasons a navigation mesh cannot be imported or loaded.
+#[derive(Debug)]
+pub enum NavMeshError {
+    /// The navigation mesh is invalid, e.g. a polygon is concave or references
+    /// a missing vertex. The landmass error identifies the faulty polygon.
+    Validation(ValidationError),
+    /// The validated navigation mesh could not be serialized.
+    Encode(String),
+    /// The data of a [`NavMesh`] row could not be deserialized.
+    Decode(String),
+    /// The height mesh does not match the navigation mesh, e.g. it has fewer
+    /// polygons or a truncated triangle.
+    InvalidHeightMesh(String),
+}
+
+impl Display for NavMeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavMeshError::Validation(err) => write!(f, "Invalid navmesh: {err:?}"),
+            NavMeshError::Encode(err) => write!(f, "Failed to encode navmesh: {err}"),
+            NavMeshError::Decode(err) => write!(f, "Failed to decode navmesh: {err}"),
+            NavMeshError::InvalidHeightMesh(err) => write!(f, "Invalid height mesh: {err}"),
+        }
+    }
+}
+
 #[table(accessor = steng_navmesh)]
 pub struct NavMesh {
     #[primary_key]
@@ -14,6 +41,13 @@ pub struct NavMesh {
     pub translation: Vec3,
     pub rotation: f32,
     pub data: Vec<u8>,
+    /// Incremented on every update, used to know when the navigation mesh must be
+    /// reloaded by the navigation tick.
+    ///
+    /// Always update rows through [`WorldEntity::update`]: rows updated through
+    /// the table directly keep their revision and are only reloaded if the
+    /// length of their data or their transform changed.
+    pub revision: u64,
 }
 
 impl WorldEntity for NavMesh {
@@ -38,7 +72,8 @@ impl WorldEntity for NavMesh {
         ctx.db.steng_navmesh().world_id().filter(world_id).collect()
     }
 
-    fn update(self, ctx: &ReducerContext) -> Self {
+    fn update(mut self, ctx: &ReducerContext) -> Self {
+        self.revision += 1;
         ctx.db.steng_navmesh().id().update(self)
     }
 
@@ -60,3 +95,12 @@ impl WorldEntity for NavMesh {
         ctx.db.steng_navmesh().world_id().filter(world_id).count()
     }
 }
+
+impl NavMesh {
+    /// Deserializes the validated navigation mesh stored in the row.
+    pub fn decode(&self) -> Result<ValidNavigationMesh<XYZ>, NavMeshError> {
+        bincode::serde::decode_from_slice(&self.data, bincode::config::standard())
+            .map(|(nav_mesh, _)| nav_mesh)
+            .map_err(|err| NavMeshError::Decode(err.to_string()))
+    }
+}
@@ -0,0 +1,70 @@
+use spacetimedb::ReducerContext;
+
+use crate::{
+    collisions::{Collider, CollisionQuery, RigidBody},
+    math::Quat,
+    spells::{ActiveCast, AreaShape, AreaTargeting, EntityId, SpellExecutor, TargetInstance},
+    utils::WorldEntity,
+    world::{World, WorldId},
+};
+
+impl AreaShape {
+    /// Builds the collider used to query the entities inside the area.
+    /// Circles are vertical cylinders as tall as they are wide, rectangles are
+    /// cuboids of the given size, their length (Z) facing away from the caster.
+    pub fn to_collider(&self, world_id: WorldId) -> Collider {
+        match self {
+            AreaShape::Circle(radius) => Collider::cylinder(world_id, *radius, *radius * 2.0),
+            AreaShape::Rectangle(size) => Collider::cuboid(world_id, *size),
+        }
+    }
+}
+
+/// Returns the entities whose rigid bodies overlap the spell's area, centered on
+/// the cast target. Only rigid bodies with an `external_id` are returned.
+///
+/// The rigid bodies are queried through `collision_query`, built on first use so
+/// that every area resolved in the same tick shares one snapshot of the world.
+pub(crate) fn resolve_area_targets(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    collision_query: &mut Option<CollisionQuery>,
+    area: &AreaTargeting,
+    cast: &ActiveCast,
+) -> Vec<EntityId> {
+    let caster_position = executor.position(ctx, world, cast.caster);
+    let center = match cast.target {
+        TargetInstance::Position(position) => Some(position),
+        TargetInstance::Entity(entity) => executor.position(ctx, world, entity),
+        TargetInstance::OnSelf => caster_position,
+    };
+    let Some(center) = center else {
+        return Vec::new();
+    };
+    let rotation = caster_position
+        .map(|caster_position| Quat::facing(center - caster_position))
+        .unwrap_or(Quat::IDENTITY);
+
+    let collider = area.shape.to_collider(world.id);
+    let mut targets = collision_query
+        .get_or_insert_with(|| CollisionQuery::new(ctx, world))
+        .overlaps(&collider, center, rotation)
+        .into_iter()
+        .filter_map(|rigid_body_id| RigidBody::find(ctx, rigid_body_id)?.external_id)
+        .collect::<Vec<_>>();
+    targets.sort_unstable();
+    targets.dedup();
+
+    if world.debug_spells {
+        log::debug!(
+            "[World#{}] [Spells] Area of cast#{} at {} hit {:?}",
+            world.id,
+            cast.id,
+            center,
+            targets
+        );
+    }
+
+    targets
+}
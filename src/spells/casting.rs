@@ -0,0 +1,367 @@
+use std::fmt::Display;
+
+use spacetimedb::ReducerContext;
+
+use crate::{
+    collisions::CollisionQuery,
+    spells::{
+        ActiveCast, CastFlags, Cooldown, EffectContext, EffectTiming, EntityId,
+        GLOBAL_COOLDOWN_SPELL_ID, ResourceId, SchoolId, SchoolLockout, SpellDef, SpellExecutor,
+        SpellId, TargetInstance, TargetingSpec,
+        area::resolve_area_targets,
+        effects::{fire_effects, fire_periodic_effects},
+        projectile::spawn_projectile,
+    },
+    utils::{Entity, WorldEntity, now_ms},
+    world::{World, WorldId},
+};
+
+/// The reasons a spell cast can be rejected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CastError {
+    /// The world does not exist.
+    WorldNotFound(WorldId),
+    /// The spell does not exist.
+    SpellNotFound(SpellId),
+    /// The executor does not know the position of this entity.
+    EntityNotFound(EntityId),
+    /// The caster is already casting another spell.
+    AlreadyCasting,
+    /// The caster is on global cooldown until the given timestamp (ms).
+    OnGlobalCooldown(i64),
+    /// The spell is on cooldown until the given timestamp (ms).
+    OnCooldown(i64),
+    /// The spell's school is locked out until the given timestamp (ms).
+    SchoolLocked(SchoolId, i64),
+    /// The target does not match the spell's targeting rules.
+    InvalidTarget,
+    /// The target is further away than the spell's range.
+    OutOfRange,
+    /// The caster does not have enough of the resource.
+    NotEnoughResource(ResourceId),
+}
+
+impl Display for CastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CastError::WorldNotFound(id) => write!(f, "World#{id} not found"),
+            CastError::SpellNotFound(id) => write!(f, "Spell#{id} not found"),
+            CastError::EntityNotFound(id) => write!(f, "Entity#{id} not found"),
+            CastError::AlreadyCasting => write!(f, "Already casting"),
+            CastError::OnGlobalCooldown(ready_at) => {
+                write!(f, "On global cooldown until {ready_at}")
+            }
+            CastError::OnCooldown(ready_at) => write!(f, "On cooldown until {ready_at}"),
+            CastError::SchoolLocked(school, ends_at) => {
+                write!(f, "School#{school} locked out until {ends_at}")
+            }
+            CastError::InvalidTarget => write!(f, "Invalid target"),
+            CastError::OutOfRange => write!(f, "Target out of range"),
+            CastError::NotEnoughResource(id) => write!(f, "Not enough of resource#{id}"),
+        }
+    }
+}
+
+/// Starts casting a spell.
+///
+/// Range, cooldowns, global cooldown, school lockouts and resource costs are
+/// validated first. Resources are spent and the global cooldown starts as soon as
+/// the cast begins, `OnCast` effects fire immediately. Instant spells are completed
+/// right away, others are completed by [`crate::spells::tick_spells`] once
+/// `cast_time_ms` elapsed. Channelled spells fire their periodic effects on their
+/// targets until then.
+pub fn cast_spell(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    executor: &mut impl SpellExecutor,
+    caster: EntityId,
+    spell_id: SpellId,
+    target: TargetInstance,
+) -> Result<ActiveCast, CastError> {
+    let world = World::find(ctx, world_id).ok_or(CastError::WorldNotFound(world_id))?;
+    let spell = SpellDef::find(ctx, spell_id as u64).ok_or(CastError::SpellNotFound(spell_id))?;
+    let now = now_ms(ctx);
+
+    if ActiveCast::find_by_caster(ctx, world_id, caster).is_some() {
+        return Err(CastError::AlreadyCasting);
+    }
+
+    if let Some(gcd) = Cooldown::find_for(ctx, world_id, caster, GLOBAL_COOLDOWN_SPELL_ID)
+        && gcd.ready_at_ms > now
+    {
+        return Err(CastError::OnGlobalCooldown(gcd.ready_at_ms));
+    }
+
+    if let Some(cooldown) = Cooldown::find_for(ctx, world_id, caster, spell_id)
+        && cooldown.ready_at_ms > now
+    {
+        return Err(CastError::OnCooldown(cooldown.ready_at_ms));
+    }
+
+    if let Some(lockout) = SchoolLockout::find_for(ctx, world_id, caster, spell.school)
+        && lockout.ends_at_ms > now
+    {
+        return Err(CastError::SchoolLocked(spell.school, lockout.ends_at_ms));
+    }
+
+    let target = validate_target(ctx, &world, executor, caster, &spell, target)?;
+
+    for cost in &spell.resource_costs {
+        if !executor.has_resource(ctx, &world, caster, cost.resource, cost.amount) {
+            return Err(CastError::NotEnoughResource(cost.resource));
+        }
+    }
+
+    for cost in &spell.resource_costs {
+        executor.spend_resource(ctx, &world, caster, cost.resource, cost.amount);
+    }
+
+    if spell.gcd_ms > 0 {
+        Cooldown::start(
+            ctx,
+            world.id,
+            caster,
+            GLOBAL_COOLDOWN_SPELL_ID,
+            now + spell.gcd_ms as i64,
+        );
+    }
+
+    let cast = ActiveCast {
+        id: 0,
+        world_id: world.id,
+        caster,
+        spell_id,
+        target,
+        started_at_ms: now,
+        will_end_at_ms: now + spell.cast_time_ms.max(0) as i64,
+        next_tick_at_ms: if spell.channeled {
+            spell.next_periodic_tick(None, 0).map(|next| now + next)
+        } else {
+            None
+        },
+        flags: CastFlags {
+            is_channel: spell.channeled,
+            interrupted: false,
+        },
+    }
+    .insert(ctx);
+
+    if world.debug_spells {
+        log::debug!(
+            "[World#{}] [Spells] Entity#{} started casting Spell#{} (cast#{})",
+            world.id,
+            caster,
+            spell_id,
+            cast.id
+        );
+    }
+
+    let effect_ctx = EffectContext {
+        caster,
+        spell: &spell,
+        target: &cast.target,
+    };
+    let mut collision_query = None;
+    let targets = resolve_targets(ctx, &world, executor, &mut collision_query, &spell, &cast);
+    fire_effects(
+        ctx,
+        &world,
+        executor,
+        &effect_ctx,
+        &targets,
+        &EffectTiming::OnCast,
+    );
+
+    if spell.is_instant() {
+        complete_cast(ctx, &world, executor, &mut collision_query, &spell, &cast);
+    }
+
+    Ok(cast)
+}
+
+/// Completes a cast: fires its `OnCastComplete` and `OnHit` effects, starts the
+/// spell cooldown and removes the cast. Projectile spells fire a projectile
+/// instead, their `OnHit` effects firing when it hits something.
+pub(crate) fn complete_cast(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    collision_query: &mut Option<CollisionQuery>,
+    spell: &SpellDef,
+    cast: &ActiveCast,
+) {
+    let effect_ctx = EffectContext {
+        caster: cast.caster,
+        spell,
+        target: &cast.target,
+    };
+    let targets = resolve_targets(ctx, world, executor, collision_query, spell, cast);
+
+    fire_effects(
+        ctx,
+        world,
+        executor,
+        &effect_ctx,
+        &targets,
+        &EffectTiming::OnCastComplete,
+    );
+    match &spell.projectile {
+        Some(projectile) => {
+            spawn_projectile(ctx, world, executor, projectile, cast, now_ms(ctx));
+        }
+        None => fire_effects(
+            ctx,
+            world,
+            executor,
+            &effect_ctx,
+            &targets,
+            &EffectTiming::OnHit,
+        ),
+    }
+
+    if spell.cooldown_ms > 0 {
+        Cooldown::start(
+            ctx,
+            world.id,
+            cast.caster,
+            spell.id,
+            now_ms(ctx) + spell.cooldown_ms as i64,
+        );
+    }
+
+    if world.debug_spells {
+        log::debug!(
+            "[World#{}] [Spells] Entity#{} completed Spell#{} (cast#{})",
+            world.id,
+            cast.caster,
+            spell.id,
+            cast.id
+        );
+    }
+
+    cast.delete(ctx);
+}
+
+/// Fires the periodic effects of a channelled cast that are due, up to the end
+/// of the channel. Returns true if the cast was ticked.
+pub(crate) fn tick_channel(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    collision_query: &mut Option<CollisionQuery>,
+    spell: &SpellDef,
+    cast: &mut ActiveCast,
+    now: i64,
+) -> bool {
+    let effect_ctx = EffectContext {
+        caster: cast.caster,
+        spell,
+        target: &cast.target,
+    };
+    let targets = resolve_targets(ctx, world, executor, collision_query, spell, cast);
+    let mut next_tick_at_ms = cast.next_tick_at_ms;
+    let mut ticked = false;
+    while let Some(tick_at) = next_tick_at_ms
+        && tick_at <= now
+        && tick_at <= cast.will_end_at_ms
+    {
+        fire_periodic_effects(
+            ctx,
+            world,
+            executor,
+            &effect_ctx,
+            &targets,
+            None,
+            tick_at - cast.started_at_ms,
+        );
+        next_tick_at_ms = spell
+            .next_periodic_tick(None, tick_at - cast.started_at_ms)
+            .map(|next| cast.started_at_ms + next);
+        ticked = true;
+    }
+
+    cast.next_tick_at_ms = next_tick_at_ms;
+    ticked
+}
+
+/// Returns the entities the effects of a cast apply to.
+/// Area spells apply their effects to every entity inside the area, queried
+/// through `collision_query` (see [`resolve_area_targets`]). Position targeted
+/// spells apply their effects to the caster, the targeted position being
+/// available through the effect context.
+pub(crate) fn resolve_targets(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    collision_query: &mut Option<CollisionQuery>,
+    spell: &SpellDef,
+    cast: &ActiveCast,
+) -> Vec<EntityId> {
+    if let TargetingSpec::Area(area) = &spell.targeting {
+        return resolve_area_targets(ctx, world, executor, collision_query, area, cast);
+    }
+
+    match cast.target {
+        TargetInstance::OnSelf | TargetInstance::Position(_) => vec![cast.caster],
+        TargetInstance::Entity(entity) => vec![entity],
+    }
+}
+
+/// Checks the target against the spell's targeting rules and range, returning
+/// the target to store on the cast. Projectile spells need a position or entity
+/// to travel to and cannot target their caster.
+fn validate_target(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    caster: EntityId,
+    spell: &SpellDef,
+    target: TargetInstance,
+) -> Result<TargetInstance, CastError> {
+    if spell.projectile.is_some()
+        && (matches!(spell.targeting, TargetingSpec::SelfOnly)
+            || matches!(target, TargetInstance::OnSelf))
+    {
+        return Err(CastError::InvalidTarget);
+    }
+
+    let range = match (&spell.targeting, &target) {
+        (TargetingSpec::SelfOnly, _) => return Ok(TargetInstance::OnSelf),
+        (TargetingSpec::SingleAlly, TargetInstance::OnSelf) => return Ok(target),
+        (TargetingSpec::SingleAlly, TargetInstance::Entity(entity)) => {
+            if *entity != caster && executor.is_hostile(ctx, world, caster, *entity) {
+                return Err(CastError::InvalidTarget);
+            }
+            spell.range
+        }
+        (TargetingSpec::SingleEnemy, TargetInstance::Entity(entity)) => {
+            if *entity == caster || !executor.is_hostile(ctx, world, caster, *entity) {
+                return Err(CastError::InvalidTarget);
+            }
+            spell.range
+        }
+        (TargetingSpec::Position(position), TargetInstance::Position(_)) => position.max_radius,
+        (TargetingSpec::Area(_), TargetInstance::OnSelf) => return Ok(target),
+        (TargetingSpec::Area(area), TargetInstance::Position(_) | TargetInstance::Entity(_)) => {
+            area.max_range
+        }
+        _ => return Err(CastError::InvalidTarget),
+    };
+
+    let caster_position = executor
+        .position(ctx, world, caster)
+        .ok_or(CastError::EntityNotFound(caster))?;
+    let target_position = match target {
+        TargetInstance::Position(position) => position,
+        TargetInstance::Entity(entity) => executor
+            .position(ctx, world, entity)
+            .ok_or(CastError::EntityNotFound(entity))?,
+        TargetInstance::OnSelf => caster_position,
+    };
+
+    if caster_position.distance_squared(&target_position) > range * range {
+        return Err(CastError::OutOfRange);
+    }
+
+    Ok(target)
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use spacetimedb::{ReducerContext, SpacetimeType, Table, table};
+
+use crate::{
+    spells::{EffectContext, EffectDef, EntityId, SpellId},
+    utils::{WorldEntity, now_ms},
+    world::{World, WorldId},
+};
+
+pub type CombatEventId = u64;
+
+/// What happened when an effect was resolved.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub enum CombatEventKind {
+    /// Damage was dealt, `amount` is the damage dealt.
+    Damage,
+    /// The damage was avoided by the target (dodge, immunity...).
+    Miss,
+    /// The target was healed, `amount` is the amount healed.
+    Heal,
+    /// A stat was modified, `amount` is the delta.
+    ModifyStat,
+    /// A status was applied, `amount` is the resulting stack count.
+    ApplyStatus,
+    /// Stacks of a status were removed, `amount` is the number of stacks removed.
+    RemoveStatus,
+    /// Statuses were dispelled, `amount` is the number of statuses removed.
+    Dispel,
+    /// The target was knocked back away from the caster.
+    Knockback,
+    /// The caster was teleported to the cast target.
+    Teleport,
+    /// Archetypes were summoned, `amount` is the number of instances spawned.
+    Summon,
+    /// A game-specific effect resolved by the executor.
+    Custom,
+}
+
+/// A combat event waiting for the outcome it describes, e.g. a knockback that is
+/// only recorded once the navigation tick actually moved its target.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq)]
+pub struct PendingCombatEvent {
+    pub caster: EntityId,
+    pub target: EntityId,
+    pub spell_id: SpellId,
+    pub effect_id: u8,
+    pub kind: CombatEventKind,
+}
+
+impl PendingCombatEvent {
+    /// Records the event, timestamped now.
+    pub(crate) fn record(&self, ctx: &ReducerContext, world_id: WorldId) -> CombatEvent {
+        CombatEvent {
+            id: 0,
+            world_id,
+            caster: self.caster,
+            target: self.target,
+            spell_id: self.spell_id,
+            effect_id: self.effect_id,
+            kind: self.kind,
+            amount: 0,
+            timestamp_ms: now_ms(ctx),
+        }
+        .insert(ctx)
+    }
+}
+
+#[table(accessor = steng_spells_combat_events, public)]
+#[derive(Clone, Debug)]
+/// An append-only log of the resolved spell effects, meant to drive floating
+/// combat text and analytics through subscriptions. Events are removed after
+/// [`World::combat_event_retention_ms`].
+pub struct CombatEvent {
+    #[primary_key]
+    #[auto_inc]
+    pub id: CombatEventId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    pub caster: EntityId,
+    pub target: EntityId,
+    pub spell_id: SpellId,
+    /// The index of the effect within the spell.
+    pub effect_id: u8,
+    pub kind: CombatEventKind,
+    pub amount: i32,
+    pub timestamp_ms: i64,
+}
+
+impl WorldEntity for CombatEvent {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_combat_events().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_combat_events().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_spells_combat_events()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_combat_events()
+            .world_id()
+            .filter(world_id)
+            .map(|event| (event.id, event))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_combat_events()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_combat_events().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_combat_events().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_spells_combat_events()
+            .world_id()
+            .filter(world_id)
+            .for_each(|event| {
+                ctx.db.steng_spells_combat_events().id().delete(event.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_spells_combat_events()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+/// Records the outcome of an effect resolved on a target.
+pub(crate) fn record_combat_event(
+    ctx: &ReducerContext,
+    world: &World,
+    effect_ctx: &EffectContext,
+    target: EntityId,
+    effect: &EffectDef,
+    kind: CombatEventKind,
+    amount: i32,
+) -> CombatEvent {
+    CombatEvent {
+        id: 0,
+        world_id: world.id,
+        caster: effect_ctx.caster,
+        target,
+        spell_id: effect_ctx.spell.id,
+        effect_id: effect.id,
+        kind,
+        amount,
+        timestamp_ms: now_ms(ctx),
+    }
+    .insert(ctx)
+}
+
+/// Removes the events older than the world's retention period.
+pub(crate) fn clear_combat_events(ctx: &ReducerContext, world: &World, now: i64) {
+    let oldest = now - world.combat_event_retention_ms;
+    for event in CombatEvent::as_vec(ctx, world.id) {
+        if event.timestamp_ms < oldest {
+            event.delete(ctx);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use spacetimedb::ReducerContext;
+
+use crate::{
+    archetypes::spawn_archetype,
+    math::Vec3,
+    navigation::{NavigationAgent, knockback_agent, teleport_agent},
+    spells::{
+        CombatEventKind, EffectContext, EffectDef, EffectKind, EffectTiming, EntityId,
+        KnockbackEffect, PendingCombatEvent, SpellExecutor, SummonEntityEffect, TargetInstance,
+        combat_event::record_combat_event,
+        status::{apply_status, dispel, remove_status},
+    },
+    utils::{WorldEntity, now_ms},
+    world::World,
+};
+
+/// Distance from the summon point at which summoned instances are placed when
+/// several are summoned at once.
+const SUMMON_SPREAD_RADIUS: f32 = 1.0;
+
+/// Fires every effect of the spell matching `timing` on each target.
+pub(crate) fn fire_effects(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    targets: &[EntityId],
+    timing: &EffectTiming,
+) {
+    for effect in effect_ctx.spell.effects_at(timing) {
+        resolve_on_targets(ctx, world, executor, effect_ctx, targets, effect);
+    }
+}
+
+/// Fires the periodic effects of the spell that are due `elapsed` ms after the
+/// periodic source (status or channel) started, limited to `effect_ids` if given.
+/// An effect fires on ticks aligned with its own interval, as long as its duration
+/// (if any) has not elapsed.
+pub(crate) fn fire_periodic_effects(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    targets: &[EntityId],
+    effect_ids: Option<&[u8]>,
+    elapsed: i64,
+) {
+    for (effect, timing) in effect_ctx.spell.periodic_effects(effect_ids) {
+        if elapsed % timing.interval_ms as i64 != 0 {
+            continue;
+        }
+        if timing.duration_ms > 0 && elapsed > timing.duration_ms as i64 {
+            continue;
+        }
+
+        resolve_on_targets(ctx, world, executor, effect_ctx, targets, effect);
+    }
+}
+
+/// Applies an effect to each target. Effects acting on the caster or once per
+/// cast, like teleports and summons, are applied once on the caster whatever the
+/// number of targets.
+fn resolve_on_targets(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    targets: &[EntityId],
+    effect: &EffectDef,
+) {
+    if matches!(
+        effect.kind,
+        EffectKind::TeleportToTarget | EffectKind::SummonEntity(_)
+    ) {
+        resolve_effect(ctx, world, executor, effect_ctx, effect_ctx.caster, effect);
+        return;
+    }
+
+    for target in targets {
+        resolve_effect(ctx, world, executor, effect_ctx, *target, effect);
+    }
+}
+
+/// Applies a single effect to a target. Effects the engine does not know how to
+/// resolve are forwarded to [`SpellExecutor::apply_effect`]. The outcome is
+/// recorded as a [`crate::spells::CombatEvent`], except for knockbacks and
+/// teleports, which are recorded by the navigation tick once the agent moved.
+pub(crate) fn resolve_effect(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    target: EntityId,
+    effect: &EffectDef,
+) {
+    let caster = effect_ctx.caster;
+    let (kind, amount) = match &effect.kind {
+        EffectKind::Damage(damage) => {
+            match executor.apply_damage(
+                ctx,
+                world,
+                caster,
+                target,
+                damage.amount,
+                damage.damage_type,
+            ) {
+                Some(dealt) => (CombatEventKind::Damage, dealt),
+                None => (CombatEventKind::Miss, 0),
+            }
+        }
+        EffectKind::Heal(heal) => {
+            let amount = heal.amount.evaluate(ctx, world, executor, caster, target);
+            let healed = executor.apply_heal(ctx, world, caster, target, amount);
+            (CombatEventKind::Heal, healed)
+        }
+        EffectKind::ModifyStat(modify) => {
+            let delta = modify.delta.evaluate(ctx, world, executor, caster, target);
+            executor.modify_stat(ctx, world, target, modify.stat, delta);
+            (CombatEventKind::ModifyStat, delta)
+        }
+        EffectKind::ApplyStatus(apply) => {
+            let status = apply_status(ctx, world, effect_ctx, target, apply, now_ms(ctx));
+            (CombatEventKind::ApplyStatus, status.stacks)
+        }
+        EffectKind::RemoveStatus(remove) => (
+            CombatEventKind::RemoveStatus,
+            remove_status(ctx, world, target, remove),
+        ),
+        EffectKind::Dispel(dispel_effect) => {
+            let removed = dispel(ctx, world, executor, caster, target, dispel_effect);
+            (CombatEventKind::Dispel, removed.len() as i32)
+        }
+        EffectKind::Knockback(knockback) => {
+            apply_knockback(ctx, world, executor, effect_ctx, target, effect, knockback);
+            return;
+        }
+        EffectKind::TeleportToTarget => {
+            apply_teleport(ctx, world, executor, effect_ctx, effect);
+            return;
+        }
+        EffectKind::SummonEntity(summon) => {
+            let spawned = apply_summon(ctx, world, executor, effect_ctx, target, summon);
+            (CombatEventKind::Summon, spawned)
+        }
+        _ => {
+            executor.apply_effect(ctx, world, effect_ctx, target, effect);
+            (CombatEventKind::Custom, 0)
+        }
+    };
+
+    record_combat_event(ctx, world, effect_ctx, target, effect, kind, amount);
+}
+
+/// Pushes the target's navigation agent (linked through its `external_id`) away
+/// from the caster. Nothing happens if the target cannot be pushed.
+fn apply_knockback(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    target: EntityId,
+    effect: &EffectDef,
+    knockback: &KnockbackEffect,
+) {
+    let Some(agent) = NavigationAgent::find_by_external_id(ctx, world.id, target) else {
+        return;
+    };
+    let Some(caster_position) = executor.position(ctx, world, effect_ctx.caster) else {
+        return;
+    };
+
+    let mut direction = agent.position() - caster_position;
+    direction.y = 0.0;
+    if direction.length_squared() == 0.0 {
+        return;
+    }
+
+    let mut movement = knockback_agent(
+        ctx,
+        &agent,
+        direction,
+        knockback.distance,
+        knockback.duration_ms,
+    );
+    movement.combat_event = Some(pending_event(
+        effect_ctx,
+        target,
+        effect,
+        CombatEventKind::Knockback,
+    ));
+    movement.update(ctx);
+}
+
+/// Teleports the caster's navigation agent (linked through its `external_id`)
+/// next to the cast target. Nothing happens if the caster cannot be teleported.
+fn apply_teleport(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    effect: &EffectDef,
+) {
+    let destination = match effect_ctx.target {
+        TargetInstance::Position(position) => Some(*position),
+        TargetInstance::Entity(entity) => executor.position(ctx, world, *entity),
+        TargetInstance::OnSelf => None,
+    };
+    let Some(destination) = destination else {
+        return;
+    };
+    let Some(agent) = NavigationAgent::find_by_external_id(ctx, world.id, effect_ctx.caster) else {
+        return;
+    };
+
+    let mut movement = teleport_agent(ctx, &agent, destination);
+    movement.combat_event = Some(pending_event(
+        effect_ctx,
+        effect_ctx.caster,
+        effect,
+        CombatEventKind::Teleport,
+    ));
+    movement.update(ctx);
+}
+
+/// The combat event of a forced movement, recorded once the agent moved.
+fn pending_event(
+    effect_ctx: &EffectContext,
+    target: EntityId,
+    effect: &EffectDef,
+    kind: CombatEventKind,
+) -> PendingCombatEvent {
+    PendingCombatEvent {
+        caster: effect_ctx.caster,
+        target,
+        spell_id: effect_ctx.spell.id,
+        effect_id: effect.id,
+        kind,
+    }
+}
+
+/// Spawns `count` instances of the archetype around the cast target, or around
+/// the effect target for spells cast on self. Instances are laid out on a circle
+/// so that their bodies do not start overlapping. Returns the number of
+/// instances spawned.
+fn apply_summon(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    effect_ctx: &EffectContext,
+    target: EntityId,
+    summon: &SummonEntityEffect,
+) -> i32 {
+    let center = match effect_ctx.target {
+        TargetInstance::Position(position) => Some(*position),
+        TargetInstance::Entity(entity) => executor.position(ctx, world, *entity),
+        TargetInstance::OnSelf => executor.position(ctx, world, target),
+    };
+    let Some(center) = center else {
+        return 0;
+    };
+
+    let mut spawned_count = 0;
+    for i in 0..summon.count {
+        let offset = if summon.count > 1 {
+            let angle = std::f32::consts::TAU * i as f32 / summon.count as f32;
+            Vec3::new(angle.cos(), 0.0, angle.sin()) * SUMMON_SPREAD_RADIUS
+        } else {
+            Vec3::ZERO
+        };
+
+        let Some(entity_id) =
+            executor.summoned_entity_id(ctx, world, effect_ctx, summon.archetype_id)
+        else {
+            if world.debug_spells {
+                log::debug!(
+                    "[World#{}] [Spells] No entity ID for Archetype#{}, skipping summon",
+                    world.id,
+                    summon.archetype_id
+                );
+            }
+            continue;
+        };
+        let Some(spawned) = spawn_archetype(
+            ctx,
+            world.id,
+            summon.archetype_id,
+            center + offset,
+            entity_id,
+        ) else {
+            log::warn!(
+                "[World#{}] [Spells] Archetype#{} not found, cannot summon it",
+                world.id,
+                summon.archetype_id
+            );
+            break;
+        };
+
+        if world.debug_spells {
+            log::debug!(
+                "[World#{}] [Spells] Entity#{} summoned Archetype#{} (RigidBody#{})",
+                world.id,
+                effect_ctx.caster,
+                summon.archetype_id,
+                spawned.rigid_body.id
+            );
+        }
+
+        executor.on_summoned(ctx, world, effect_ctx, &spawned);
+        spawned_count += 1;
+    }
+
+    spawned_count
+}
@@ -0,0 +1,123 @@
+use spacetimedb::ReducerContext;
+
+use crate::{
+    archetypes::{ArchetypeId, SpawnedArchetype},
+    math::Vec3,
+    spells::{EffectDef, EntityId, ResourceId, SchoolId, SpellDef, StatId, TargetInstance},
+    world::World,
+};
+
+/// Describes the spell an effect is resolved for.
+#[derive(Clone, Debug)]
+pub struct EffectContext<'a> {
+    /// The entity that cast the spell.
+    pub caster: EntityId,
+    /// The spell being resolved.
+    pub spell: &'a SpellDef,
+    /// The target the spell was cast on.
+    pub target: &'a TargetInstance,
+}
+
+/// Hooks a game implements to let the spell runtime read and mutate entities
+/// it does not own. Spell logic stays in the engine, storage of positions,
+/// stats and resources stays in the game.
+pub trait SpellExecutor {
+    /// Returns the current position of an entity, or `None` if it does not exist.
+    fn position(&mut self, ctx: &ReducerContext, world: &World, entity: EntityId) -> Option<Vec3>;
+
+    /// Returns true if `target` is hostile to `source`.
+    fn is_hostile(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        source: EntityId,
+        target: EntityId,
+    ) -> bool;
+
+    /// Returns true if the entity has at least `amount` of the resource.
+    fn has_resource(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        entity: EntityId,
+        resource: ResourceId,
+        amount: i32,
+    ) -> bool;
+
+    /// Deducts `amount` of the resource from the entity.
+    fn spend_resource(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        entity: EntityId,
+        resource: ResourceId,
+        amount: i32,
+    );
+
+    /// Returns the current value of an entity's stat, used to evaluate amount formulas.
+    fn stat(&mut self, ctx: &ReducerContext, world: &World, entity: EntityId, stat: StatId) -> f32;
+
+    /// Adds `delta` to an entity's stat.
+    fn modify_stat(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        entity: EntityId,
+        stat: StatId,
+        delta: i32,
+    );
+
+    /// Deals `amount` damage of the given type to the target. Returns the damage
+    /// actually dealt, or `None` if the target avoided it.
+    fn apply_damage(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        source: EntityId,
+        target: EntityId,
+        amount: i32,
+        damage_type: SchoolId,
+    ) -> Option<i32>;
+
+    /// Heals the target for `amount`. Returns the amount actually healed.
+    fn apply_heal(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        source: EntityId,
+        target: EntityId,
+        amount: i32,
+    ) -> i32;
+
+    /// Applies an effect the engine does not resolve by itself to a target.
+    fn apply_effect(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        effect_ctx: &EffectContext,
+        target: EntityId,
+        effect: &EffectDef,
+    );
+
+    /// Returns the entity ID of an archetype about to be summoned by a
+    /// `SummonEntity` effect. It is set as the `external_id` of the spawned rigid
+    /// body and agent. If `None`, the archetype is not summoned.
+    fn summoned_entity_id(
+        &mut self,
+        ctx: &ReducerContext,
+        world: &World,
+        effect_ctx: &EffectContext,
+        archetype_id: ArchetypeId,
+    ) -> Option<EntityId>;
+
+    /// Called for every archetype spawned by a `SummonEntity` effect, letting the
+    /// game create its own entity and link it to the spawned rigid body and agent.
+    fn on_summoned(
+        &mut self,
+        _ctx: &ReducerContext,
+        _world: &World,
+        _effect_ctx: &EffectContext,
+        _spawned: &SpawnedArchetype,
+    ) {
+    }
+}
@@ -0,0 +1,32 @@
+use spacetimedb::ReducerContext;
+
+use crate::{
+    spells::{AmountFormula, EntityId, SpellExecutor},
+    world::World,
+};
+
+impl AmountFormula {
+    /// Computes the amount, reading stats through the executor.
+    /// `attacker` is the entity the effect comes from and `defender` the one it applies to.
+    pub fn evaluate(
+        &self,
+        ctx: &ReducerContext,
+        world: &World,
+        executor: &mut impl SpellExecutor,
+        attacker: EntityId,
+        defender: EntityId,
+    ) -> i32 {
+        match self {
+            AmountFormula::Flat(amount) => *amount,
+            AmountFormula::StatScale(scaled) => {
+                let stat = executor.stat(ctx, world, attacker, scaled.stat);
+                scaled.base + (stat * scaled.coeff).round() as i32
+            }
+            AmountFormula::AttackerVsDefender(versus) => {
+                let attacker_stat = executor.stat(ctx, world, attacker, versus.attacker_stat);
+                let defender_stat = executor.stat(ctx, world, defender, versus.defender_stat);
+                versus.base + ((attacker_stat - defender_stat) * versus.coeff).round() as i32
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{
+    navigation::{NavigationAgent, NavigationAgentId},
+    spells::{ActiveCast, EntityId, SchoolId, SpellDef},
+    utils::{Entity, WorldEntity, now_ms},
+    world::{World, WorldId},
+};
+
+pub type SchoolLockoutId = u64;
+
+/// Speed above which a casting agent is considered to be moving.
+const MOVING_SPEED_THRESHOLD: f32 = 0.01;
+
+#[table(accessor = steng_spells_school_lockouts)]
+#[derive(Clone, Debug)]
+/// Prevents an entity from casting spells of a school until `ends_at_ms`,
+/// typically after being interrupted.
+pub struct SchoolLockout {
+    #[primary_key]
+    #[auto_inc]
+    pub id: SchoolLockoutId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    #[index(btree)]
+    pub caster: EntityId,
+    pub school: SchoolId,
+    pub ends_at_ms: i64,
+}
+
+impl WorldEntity for SchoolLockout {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_school_lockouts().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_school_lockouts().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_spells_school_lockouts()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_school_lockouts()
+            .world_id()
+            .filter(world_id)
+            .map(|lockout| (lockout.id, lockout))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_school_lockouts()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_school_lockouts().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_school_lockouts().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_spells_school_lockouts()
+            .world_id()
+            .filter(world_id)
+            .for_each(|lockout| {
+                ctx.db
+                    .steng_spells_school_lockouts()
+                    .id()
+                    .delete(lockout.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_spells_school_lockouts()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl SchoolLockout {
+    /// Returns the lockout of a school for the given caster of a world, if any.
+    pub fn find_for(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        caster: EntityId,
+        school: SchoolId,
+    ) -> Option<Self> {
+        ctx.db
+            .steng_spells_school_lockouts()
+            .caster()
+            .filter(caster)
+            .find(|lockout| lockout.world_id == world_id && lockout.school == school)
+    }
+
+    /// Locks the school out for the given caster, extending any existing lockout.
+    pub fn start(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        caster: EntityId,
+        school: SchoolId,
+        ends_at_ms: i64,
+    ) -> Self {
+        match SchoolLockout::find_for(ctx, world_id, caster, school) {
+            Some(mut lockout) => {
+                lockout.ends_at_ms = lockout.ends_at_ms.max(ends_at_ms);
+                lockout.update(ctx)
+            }
+            None => SchoolLockout {
+                id: 0,
+                world_id,
+                caster,
+                school,
+                ends_at_ms,
+            }
+            .insert(ctx),
+        }
+    }
+}
+
+/// Interrupts the cast of the given caster of a world, if any. If `school_lockout_ms` is set,
+/// the caster cannot cast spells of the interrupted spell's school for that long.
+///
+/// The cast is removed right away, its remaining effects never fire and the caster
+/// can start another cast in the same tick. Returns the interrupted cast, flagged
+/// as interrupted.
+pub fn interrupt_cast(
+    ctx: &ReducerContext,
+    world_id: WorldId,
+    caster: EntityId,
+    school_lockout_ms: Option<i32>,
+) -> Option<ActiveCast> {
+    let mut cast = ActiveCast::find_by_caster(ctx, world_id, caster)?;
+    cast.delete(ctx);
+    cast.flags.interrupted = true;
+
+    if let Some(lockout_ms) = school_lockout_ms
+        && lockout_ms > 0
+        && let Some(spell) = SpellDef::find(ctx, cast.spell_id as u64)
+    {
+        SchoolLockout::start(
+            ctx,
+            cast.world_id,
+            caster,
+            spell.school,
+            now_ms(ctx) + lockout_ms as i64,
+        );
+    }
+
+    Some(cast)
+}
+
+/// Interrupts the non-instant casts of casters whose navigation agent
+/// (linked through its `external_id`) moved during this tick.
+pub(crate) fn interrupt_moving_casters(
+    ctx: &ReducerContext,
+    world: &World,
+    agents: &HashMap<NavigationAgentId, NavigationAgent>,
+) {
+    let moving: HashSet<EntityId> = agents
+        .values()
+        .filter(|agent| agent.speed() > MOVING_SPEED_THRESHOLD)
+        .filter_map(|agent| agent.external_id)
+        .collect();
+    if moving.is_empty() {
+        return;
+    }
+
+    for cast in ActiveCast::as_vec(ctx, world.id) {
+        if !moving.contains(&cast.caster) {
+            continue;
+        }
+
+        if world.debug_spells {
+            log::debug!(
+                "[World#{}] [Spells] Entity#{} moved while casting Spell#{}, interrupting cast#{}",
+                world.id,
+                cast.caster,
+                cast.spell_id,
+                cast.id
+            );
+        }
+
+        interrupt_cast(ctx, world.id, cast.caster, None);
+    }
+}
+
+/// Removes the lockouts that ended.
+pub(crate) fn clear_school_lockouts(ctx: &ReducerContext, world: &World, now: i64) {
+    for lockout in SchoolLockout::as_vec(ctx, world.id) {
+        if lockout.ends_at_ms <= now {
+            lockout.delete(ctx);
+        }
+    }
+}
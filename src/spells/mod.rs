@@ -1 +1,20 @@
+mod area;
+mod casting;
+mod combat_event;
+mod effects;
+mod executor;
+mod formula;
+mod interrupt;
+mod projectile;
 mod spell;
+mod status;
+mod tick;
+
+pub use casting::{CastError, cast_spell};
+pub use combat_event::{CombatEvent, CombatEventId, CombatEventKind, PendingCombatEvent};
+pub use executor::{EffectContext, SpellExecutor};
+pub use interrupt::{SchoolLockout, SchoolLockoutId, interrupt_cast};
+pub use projectile::{Projectile, ProjectileId};
+pub use spell::*;
+pub use status::{StackingPolicy, StatusDef, StatusKind};
+pub use tick::tick_spells;
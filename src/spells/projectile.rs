@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use spacetimedb::{ReducerContext, Table, table};
+
+use crate::{
+    collisions::{Collider, ColliderId, CollisionQuery, RayCastHit, RigidBody, RigidBodyId},
+    math::{Quat, Vec3},
+    spells::{
+        ActiveCast, EffectContext, EffectTiming, EntityId, ProjectileSpec, SpellDef, SpellExecutor,
+        SpellId, TargetInstance, effects::fire_effects,
+    },
+    utils::{Entity, WorldEntity},
+    world::{World, WorldId},
+};
+
+pub type ProjectileId = u64;
+
+#[table(accessor = steng_spells_projectiles, public)]
+#[derive(Clone, Debug)]
+/// A spell in flight. Projectiles move every tick, stop at the first rigid body
+/// they touch and fire the `OnHit` effects of their spell on the entity linked
+/// to that body.
+pub struct Projectile {
+    #[primary_key]
+    #[auto_inc]
+    /// The unique ID of the projectile.
+    pub id: ProjectileId,
+    #[index(btree)]
+    /// The world ID this projectile belongs to.
+    pub world_id: WorldId,
+
+    /// The entity that fired the projectile.
+    pub caster: EntityId,
+    /// The spell the projectile carries.
+    pub spell_id: SpellId,
+    /// The target of the cast that fired the projectile.
+    pub target: TargetInstance,
+
+    /// The current position of the projectile.
+    pub position: Vec3,
+    /// The normalized direction the projectile travels in. Homing projectiles
+    /// update it every tick to face their target.
+    pub direction: Vec3,
+    /// The speed of the projectile in units per second.
+    pub speed: f32,
+    /// The entity followed by homing projectiles.
+    pub homing_target: Option<EntityId>,
+
+    /// The collider swept to detect hits.
+    pub collider_id: ColliderId,
+
+    /// The last time the projectile moved.
+    pub updated_at_ms: i64,
+    /// The time at which the projectile vanishes if it did not hit anything.
+    pub expires_at_ms: i64,
+
+    /// The hits registered during the last update. A projectile stops at its
+    /// first hit and is removed on the next tick.
+    pub added_hits: Vec<RayCastHit>,
+}
+
+impl WorldEntity for Projectile {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_projectiles().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_projectiles().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_spells_projectiles()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_projectiles()
+            .world_id()
+            .filter(world_id)
+            .map(|projectile| (projectile.id, projectile))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_projectiles()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_projectiles().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_projectiles().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_spells_projectiles()
+            .world_id()
+            .filter(world_id)
+            .for_each(|projectile| {
+                ctx.db.steng_spells_projectiles().id().delete(projectile.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_spells_projectiles()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl Projectile {
+    /// Removes the projectile and its collider.
+    pub fn despawn(&self, ctx: &ReducerContext) {
+        if let Some(collider) = Collider::find(ctx, self.collider_id) {
+            collider.delete(ctx);
+        }
+        self.delete(ctx);
+    }
+}
+
+/// Fires a projectile from the caster towards the cast target.
+/// Returns `None` if the caster or target position is unknown.
+pub(crate) fn spawn_projectile(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    spec: &ProjectileSpec,
+    cast: &ActiveCast,
+    now: i64,
+) -> Option<Projectile> {
+    let origin = executor.position(ctx, world, cast.caster)?;
+    let (target_position, homing_target) = match cast.target {
+        TargetInstance::Entity(entity) => (
+            executor.position(ctx, world, entity)?,
+            spec.homing.then_some(entity),
+        ),
+        TargetInstance::Position(position) => (position, None),
+        TargetInstance::OnSelf => return None,
+    };
+
+    let collider = Collider::sphere(world.id, spec.radius).insert(ctx);
+    let projectile = Projectile {
+        id: 0,
+        world_id: world.id,
+        caster: cast.caster,
+        spell_id: cast.spell_id,
+        target: cast.target.clone(),
+        position: origin,
+        direction: (target_position - origin).normalize(),
+        speed: spec.speed,
+        homing_target,
+        collider_id: collider.id,
+        updated_at_ms: now,
+        expires_at_ms: now + spec.max_lifetime_ms as i64,
+        added_hits: Vec::new(),
+    }
+    .insert(ctx);
+
+    if world.debug_spells {
+        log::debug!(
+            "[World#{}] [Spells] Entity#{} fired projectile#{} (Spell#{})",
+            world.id,
+            cast.caster,
+            projectile.id,
+            cast.spell_id
+        );
+    }
+
+    Some(projectile)
+}
+
+/// Moves the projectiles of the world, sweeping their collider against the rigid
+/// bodies. On the first hit the `OnHit` effects of the spell fire on the entity
+/// linked to the body hit (through its `external_id`). Projectiles that hit
+/// something during the previous tick or outlived their lifetime are removed.
+pub(crate) fn tick_projectiles(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    now: i64,
+) {
+    let projectiles = Projectile::as_vec(ctx, world.id);
+    if projectiles.is_empty() {
+        return;
+    }
+
+    let query = CollisionQuery::new(ctx, world);
+    let mut bodies_by_entity: HashMap<EntityId, Vec<RigidBodyId>> = HashMap::new();
+    let mut external_ids: HashMap<RigidBodyId, EntityId> = HashMap::new();
+    for rigid_body in RigidBody::iter(ctx, world.id) {
+        if let Some(external_id) = rigid_body.external_id {
+            bodies_by_entity
+                .entry(external_id)
+                .or_default()
+                .push(rigid_body.id);
+            external_ids.insert(rigid_body.id, external_id);
+        }
+    }
+
+    for mut projectile in projectiles {
+        if !projectile.added_hits.is_empty() || projectile.updated_at_ms >= projectile.expires_at_ms
+        {
+            projectile.despawn(ctx);
+            continue;
+        }
+
+        let Some(collider) = Collider::find(ctx, projectile.collider_id) else {
+            projectile.delete(ctx);
+            continue;
+        };
+
+        if let Some(target) = projectile.homing_target
+            && let Some(target_position) = executor.position(ctx, world, target)
+        {
+            projectile.direction = (target_position - projectile.position).normalize();
+        }
+
+        let delta_time =
+            (now.min(projectile.expires_at_ms) - projectile.updated_at_ms) as f32 / 1_000.0;
+        let motion = projectile.direction * projectile.speed * delta_time;
+        let ignored = bodies_by_entity
+            .get(&projectile.caster)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let hit = query.sweep(
+            &collider,
+            projectile.position,
+            Quat::IDENTITY,
+            motion,
+            ignored,
+        );
+
+        projectile.updated_at_ms = now.min(projectile.expires_at_ms);
+        match hit {
+            Some(hit) => {
+                projectile.position += projectile.direction * hit.distance;
+                if let Some(target) = external_ids.get(&hit.rigid_body_id)
+                    && let Some(spell) = SpellDef::find(ctx, projectile.spell_id as u64)
+                {
+                    let effect_ctx = EffectContext {
+                        caster: projectile.caster,
+                        spell: &spell,
+                        target: &projectile.target,
+                    };
+                    fire_effects(
+                        ctx,
+                        world,
+                        executor,
+                        &effect_ctx,
+                        &[*target],
+                        &EffectTiming::OnHit,
+                    );
+                }
+
+                if world.debug_spells {
+                    log::debug!(
+                        "[World#{}] [Spells] Projectile#{} hit RigidBody#{} at {}",
+                        world.id,
+                        projectile.id,
+                        hit.rigid_body_id,
+                        hit.position
+                    );
+                }
+                projectile.added_hits = vec![hit];
+            }
+            None => projectile.position += motion,
+        }
+
+        projectile.update(ctx);
+    }
+}
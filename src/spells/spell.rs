@@ -1,6 +1,12 @@
-use spacetimedb::{SpacetimeType, table};
+use std::collections::HashMap;
 
-use crate::math::Vec3;
+use spacetimedb::{ReducerContext, SpacetimeType, Table, table};
+
+use crate::{
+    math::Vec3,
+    utils::{Entity, WorldEntity},
+    world::WorldId,
+};
 
 pub type SpellId = u32;
 pub type SchoolId = u16;
@@ -9,29 +15,47 @@ pub type StatId = u16; // e.g. Hp, Mana, Strength, etc
 pub type ResourceId = u16;
 pub type StatusId = u16; // buff/debuff IDs
 pub type EntityId = u64; // your game’s entity key
+pub type ActiveCastId = u64;
+pub type CooldownId = u64;
+pub type StatusInstanceId = u64;
+
+/// The spell ID used to store the global cooldown of a caster in the cooldowns table.
+pub const GLOBAL_COOLDOWN_SPELL_ID: SpellId = SpellId::MAX;
 
 #[table(accessor = steng_spells)]
+#[derive(Clone, Debug)]
 pub struct SpellDef {
+    #[primary_key]
     pub id: SpellId,
     pub name: String,
     pub school: SchoolId,  // e.g. Fire, Arcane, Support
     pub tags: Vec<TagId>,  // AoE, SingleTarget, Projectile, Dispellable, etc.
-    pub cast_time_ms: i32, // 0 = instant
+    pub cast_time_ms: i32, // 0 = instant, channel duration for channelled spells
+    pub channeled: bool,   // fires periodic effects while casting
     pub cooldown_ms: i32,
     pub gcd_ms: i32,                       // global cooldown, if you want that
     pub range: f32,                        // generic “max range”
     pub resource_costs: Vec<ResourceCost>, // mana/energy/stamina etc
     pub targeting: TargetingSpec,
     pub effects: Vec<EffectDef>,
+    pub projectile: Option<ProjectileSpec>, // travels to the target before firing OnHit effects
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct ProjectileSpec {
+    pub speed: f32,  // units per second
+    pub radius: f32, // radius of the projectile's sphere collider
+    pub max_lifetime_ms: i32,
+    pub homing: bool, // follows the target entity instead of flying straight
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct ResourceCost {
     pub resource: ResourceId,
     pub amount: i32,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub enum TargetingSpec {
     SelfOnly,
     SingleAlly,
@@ -40,32 +64,31 @@ pub enum TargetingSpec {
     Area(AreaTargeting),
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct PositionTargeting {
     pub max_radius: f32,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct AreaTargeting {
-    pub shape: AreaShape,
-    pub radius: f32,
+    pub shape: AreaShape, // size of the area, e.g. the radius of a circle
     pub max_range: f32,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub enum AreaShape {
     Circle(f32),
     Rectangle(Vec3), // width, height no Z -> Should I get a Vec2 ?
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct EffectDef {
     pub id: u8,             // index within spell
     pub when: EffectTiming, // OnCast, OnHit, Periodic tick, OnExpire…
     pub kind: EffectKind,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub enum EffectTiming {
     OnCast,
     OnCastComplete,
@@ -74,13 +97,13 @@ pub enum EffectTiming {
     OnExpire,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct PeriodicEffectTiming {
     pub interval_ms: i32,
     pub duration_ms: i32,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub enum EffectKind {
     Damage(DamageEffect),
     Heal(HealEffect),
@@ -88,65 +111,68 @@ pub enum EffectKind {
     ApplyStatus(ApplyStatusEffect),
     RemoveStatus(RemoveStatusEffect),
     Dispel(DispelEffect),
-    TeleportToTarget, // interpret with game hooks
+    TeleportToTarget, // moves the caster's navigation agent to the cast target
     Knockback(KnockbackEffect),
     SummonEntity(SummonEntityEffect),
     Custom(CustomEffect), // extension point for game-specific logic
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct DamageEffect {
     pub amount: i32,
     pub damage_type: SchoolId,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct HealEffect {
     pub amount: AmountFormula,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct ModifyStatEffect {
     pub stat: StatId,
     pub delta: AmountFormula,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct ApplyStatusEffect {
     pub status_id: StatusId,
     pub duration_ms: i32,
     pub stacks: i32,
+    /// Ids of the spell's periodic effects the status fires while it lasts.
+    pub periodic_effects: Vec<u8>,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct RemoveStatusEffect {
     pub status_id: StatusId,
     pub stacks: i32,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct DispelEffect {
     pub max_statuses: u8,
     pub filter_tags: Vec<TagId>,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct KnockbackEffect {
     pub distance: f32,
+    pub duration_ms: i32, // 0 = instant
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct SummonEntityEffect {
     pub archetype_id: u32,
     pub count: u8,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct CustomEffect {
     pub code: u16,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub enum AmountFormula {
     Flat(i32),
     StatScale(StatScaled),
@@ -154,14 +180,14 @@ pub enum AmountFormula {
     // You can add “RandomRange” or “CritChance” as needed via the engine.
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct StatScaled {
     pub stat: StatId,
     pub coeff: f32,
     pub base: i32,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct AttackerVsDefender {
     pub attacker_stat: StatId,
     pub defender_stat: StatId,
@@ -170,23 +196,30 @@ pub struct AttackerVsDefender {
 }
 
 #[table(accessor = steng_spells_active_casts)]
+#[derive(Clone, Debug)]
 pub struct ActiveCast {
-    pub id: u64,
+    #[primary_key]
+    #[auto_inc]
+    pub id: ActiveCastId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    #[index(btree)]
     pub caster: EntityId,
     pub spell_id: SpellId,
     pub target: TargetInstance,
     pub started_at_ms: i64,
     pub will_end_at_ms: i64,
+    pub next_tick_at_ms: Option<i64>, // next periodic tick of a channel
     pub flags: CastFlags,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub struct CastFlags {
     pub is_channel: bool,
     pub interrupted: bool,
 }
 
-#[derive(SpacetimeType)]
+#[derive(SpacetimeType, Clone, Debug)]
 pub enum TargetInstance {
     OnSelf,
     Entity(EntityId),
@@ -194,16 +227,29 @@ pub enum TargetInstance {
 }
 
 #[table(accessor = steng_spells_cooldowns)]
+#[derive(Clone, Debug)]
 pub struct Cooldown {
+    #[primary_key]
+    #[auto_inc]
+    pub id: CooldownId,
+    #[index(btree)]
+    pub world_id: WorldId,
+    #[index(btree)]
     pub caster: EntityId,
     pub spell_id: SpellId,
     pub ready_at_ms: i64,
 }
 
 #[table(accessor = steng_spells_status_instances)]
+#[derive(Clone, Debug)]
 pub struct StatusInstance {
-    pub id: u64,
+    #[primary_key]
+    #[auto_inc]
+    pub id: StatusInstanceId,
+    #[index(btree)]
+    pub world_id: WorldId,
     pub status_id: StatusId,
+    #[index(btree)]
     pub owner: EntityId,
     pub source: EntityId, // caster
     pub applied_at_ms: i64,
@@ -212,4 +258,367 @@ pub struct StatusInstance {
     pub periodic_interval_ms: Option<i32>,
     pub next_tick_at_ms: Option<i64>,
     pub spell_id: Option<SpellId>, // for reference
+    pub periodic_effects: Vec<u8>, // ids of the spell's periodic effects fired by the status
+}
+
+impl SpellDef {
+    /// Returns the effects of this spell that should fire at the given timing.
+    pub fn effects_at<'a>(
+        &'a self,
+        timing: &'a EffectTiming,
+    ) -> impl Iterator<Item = &'a EffectDef> + 'a {
+        self.effects
+            .iter()
+            .filter(move |effect| effect.when.same_kind(timing))
+    }
+
+    /// Returns true if the spell completes as soon as it is cast.
+    pub fn is_instant(&self) -> bool {
+        self.cast_time_ms <= 0
+    }
+
+    /// Returns the periodic effects of this spell with a positive interval, limited
+    /// to the given effect ids if any.
+    pub fn periodic_effects<'a>(
+        &'a self,
+        effect_ids: Option<&'a [u8]>,
+    ) -> impl Iterator<Item = (&'a EffectDef, &'a PeriodicEffectTiming)> + 'a {
+        self.effects
+            .iter()
+            .filter_map(move |effect| match &effect.when {
+                EffectTiming::Periodic(timing)
+                    if timing.interval_ms > 0
+                        && effect_ids.is_none_or(|ids| ids.contains(&effect.id)) =>
+                {
+                    Some((effect, timing))
+                }
+                _ => None,
+            })
+    }
+
+    /// Returns the time, relative to the start of the periodic effects, at which
+    /// the next of them fires after `elapsed` ms. Each effect fires on multiples of
+    /// its own interval until its duration elapses.
+    pub fn next_periodic_tick(&self, effect_ids: Option<&[u8]>, elapsed: i64) -> Option<i64> {
+        self.periodic_effects(effect_ids)
+            .filter_map(|(_, timing)| {
+                let interval = timing.interval_ms as i64;
+                let next = (elapsed.max(0) / interval + 1) * interval;
+                (timing.duration_ms <= 0 || next <= timing.duration_ms as i64).then_some(next)
+            })
+            .min()
+    }
+}
+
+impl EffectTiming {
+    /// Returns true if both timings are the same variant, ignoring their parameters.
+    pub fn same_kind(&self, other: &EffectTiming) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl Entity for SpellDef {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells().id().find(id as SpellId)
+    }
+
+    fn iter(ctx: &ReducerContext) -> impl Iterator<Item = Self> {
+        ctx.db.steng_spells().iter()
+    }
+
+    fn as_map(ctx: &ReducerContext) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells()
+            .iter()
+            .map(|spell| (spell.id as u64, spell))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext) -> Vec<Self> {
+        ctx.db.steng_spells().iter().collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext) {
+        ctx.db.steng_spells().iter().for_each(|spell| {
+            ctx.db.steng_spells().id().delete(spell.id);
+        });
+    }
+
+    fn count(ctx: &ReducerContext) -> u64 {
+        ctx.db.steng_spells().count()
+    }
+}
+
+impl WorldEntity for ActiveCast {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_active_casts().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_active_casts().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_spells_active_casts()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_active_casts()
+            .world_id()
+            .filter(world_id)
+            .map(|cast| (cast.id, cast))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_active_casts()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_active_casts().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_active_casts().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_spells_active_casts()
+            .world_id()
+            .filter(world_id)
+            .for_each(|cast| {
+                ctx.db.steng_spells_active_casts().id().delete(cast.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_spells_active_casts()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl ActiveCast {
+    /// Returns the cast currently performed by the given caster of a world, if any.
+    pub fn find_by_caster(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        caster: EntityId,
+    ) -> Option<Self> {
+        ctx.db
+            .steng_spells_active_casts()
+            .caster()
+            .filter(caster)
+            .find(|cast| cast.world_id == world_id)
+    }
+}
+
+impl WorldEntity for Cooldown {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_cooldowns().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_cooldowns().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db.steng_spells_cooldowns().world_id().filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_cooldowns()
+            .world_id()
+            .filter(world_id)
+            .map(|cooldown| (cooldown.id, cooldown))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_cooldowns()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_cooldowns().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_cooldowns().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_spells_cooldowns()
+            .world_id()
+            .filter(world_id)
+            .for_each(|cooldown| {
+                ctx.db.steng_spells_cooldowns().id().delete(cooldown.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_spells_cooldowns()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
+}
+
+impl Cooldown {
+    /// Returns the cooldown of the given caster of a world for a spell, if any.
+    pub fn find_for(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        caster: EntityId,
+        spell_id: SpellId,
+    ) -> Option<Self> {
+        ctx.db
+            .steng_spells_cooldowns()
+            .caster()
+            .filter(caster)
+            .find(|cooldown| cooldown.world_id == world_id && cooldown.spell_id == spell_id)
+    }
+
+    /// Starts or restarts the cooldown of a spell for the given caster.
+    pub fn start(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        caster: EntityId,
+        spell_id: SpellId,
+        ready_at_ms: i64,
+    ) -> Self {
+        match Cooldown::find_for(ctx, world_id, caster, spell_id) {
+            Some(mut cooldown) => {
+                cooldown.ready_at_ms = cooldown.ready_at_ms.max(ready_at_ms);
+                cooldown.update(ctx)
+            }
+            None => Cooldown {
+                id: 0,
+                world_id,
+                caster,
+                spell_id,
+                ready_at_ms,
+            }
+            .insert(ctx),
+        }
+    }
+}
+
+impl StatusInstance {
+    /// Returns the instances of a status owned by an entity of a world.
+    pub fn find_for(
+        ctx: &ReducerContext,
+        world_id: WorldId,
+        owner: EntityId,
+        status_id: StatusId,
+    ) -> Vec<Self> {
+        ctx.db
+            .steng_spells_status_instances()
+            .owner()
+            .filter(owner)
+            .filter(|status| status.world_id == world_id && status.status_id == status_id)
+            .collect()
+    }
+
+    /// Returns every status instance owned by an entity of a world.
+    pub fn for_owner(ctx: &ReducerContext, world_id: WorldId, owner: EntityId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_status_instances()
+            .owner()
+            .filter(owner)
+            .filter(|status| status.world_id == world_id)
+            .collect()
+    }
+}
+
+impl WorldEntity for StatusInstance {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_status_instances().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_status_instances().id().find(id)
+    }
+
+    fn iter(ctx: &ReducerContext, world_id: WorldId) -> impl Iterator<Item = Self> {
+        ctx.db
+            .steng_spells_status_instances()
+            .world_id()
+            .filter(world_id)
+    }
+
+    fn as_map(ctx: &ReducerContext, world_id: WorldId) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_status_instances()
+            .world_id()
+            .filter(world_id)
+            .map(|status| (status.id, status))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext, world_id: WorldId) -> Vec<Self> {
+        ctx.db
+            .steng_spells_status_instances()
+            .world_id()
+            .filter(world_id)
+            .collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_status_instances().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_status_instances().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext, world_id: WorldId) {
+        ctx.db
+            .steng_spells_status_instances()
+            .world_id()
+            .filter(world_id)
+            .for_each(|status| {
+                ctx.db
+                    .steng_spells_status_instances()
+                    .id()
+                    .delete(status.id);
+            });
+    }
+
+    fn count(ctx: &ReducerContext, world_id: WorldId) -> usize {
+        ctx.db
+            .steng_spells_status_instances()
+            .world_id()
+            .filter(world_id)
+            .count()
+    }
 }
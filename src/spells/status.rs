@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use spacetimedb::{ReducerContext, SpacetimeType, Table, table};
+
+use crate::{
+    spells::{
+        ApplyStatusEffect, DispelEffect, EffectContext, EffectTiming, EntityId, RemoveStatusEffect,
+        SpellDef, SpellExecutor, StatusId, StatusInstance, TagId, TargetInstance,
+        effects::{fire_periodic_effects, resolve_effect},
+    },
+    utils::{Entity, WorldEntity},
+    world::World,
+};
+
+/// Whether a status is beneficial or harmful to its owner.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq, Default)]
+pub enum StatusKind {
+    #[default]
+    Buff,
+    Debuff,
+}
+
+/// How applying a status to an entity that already has it behaves.
+#[derive(SpacetimeType, Clone, Copy, Debug, PartialEq, Default)]
+pub enum StackingPolicy {
+    /// The existing instance has its duration refreshed, stacks are not added.
+    #[default]
+    Refresh,
+    /// Stacks are added to the existing instance up to the status' max stacks,
+    /// and its duration is refreshed.
+    AddStacks,
+    /// Every application creates a new, independent instance.
+    Independent,
+}
+
+#[table(accessor = steng_spells_status_defs)]
+#[derive(Clone, Debug)]
+/// Describes a status (buff or debuff). Statuses without a definition use the
+/// default stacking policy, have no stack limit and cannot be dispelled.
+pub struct StatusDef {
+    #[primary_key]
+    pub id: StatusId,
+    pub name: String,
+    /// Tags used to match the status, e.g. by dispel effects (Magic, Poison, Curse, etc).
+    pub tags: Vec<TagId>,
+    /// Whether the status is a buff or a debuff.
+    pub kind: StatusKind,
+    /// Statuses with a higher priority are dispelled first.
+    pub priority: i32,
+    /// How re-applying the status behaves.
+    pub stacking: StackingPolicy,
+    /// The maximum number of stacks a single instance can have.
+    pub max_stacks: i32,
+}
+
+impl Entity for StatusDef {
+    fn insert(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_status_defs().insert(self)
+    }
+
+    fn find(ctx: &ReducerContext, id: u64) -> Option<Self> {
+        ctx.db.steng_spells_status_defs().id().find(id as StatusId)
+    }
+
+    fn iter(ctx: &ReducerContext) -> impl Iterator<Item = Self> {
+        ctx.db.steng_spells_status_defs().iter()
+    }
+
+    fn as_map(ctx: &ReducerContext) -> HashMap<u64, Self> {
+        ctx.db
+            .steng_spells_status_defs()
+            .iter()
+            .map(|status| (status.id as u64, status))
+            .collect()
+    }
+
+    fn as_vec(ctx: &ReducerContext) -> Vec<Self> {
+        ctx.db.steng_spells_status_defs().iter().collect()
+    }
+
+    fn update(self, ctx: &ReducerContext) -> Self {
+        ctx.db.steng_spells_status_defs().id().update(self)
+    }
+
+    fn delete(&self, ctx: &ReducerContext) {
+        ctx.db.steng_spells_status_defs().id().delete(self.id);
+    }
+
+    fn clear(ctx: &ReducerContext) {
+        ctx.db.steng_spells_status_defs().iter().for_each(|status| {
+            ctx.db.steng_spells_status_defs().id().delete(status.id);
+        });
+    }
+
+    fn count(ctx: &ReducerContext) -> u64 {
+        ctx.db.steng_spells_status_defs().count()
+    }
+}
+
+/// Applies a status to the target following the status' stacking policy.
+/// Unless the spell is channelled, the status fires the periodic effects listed by
+/// the `ApplyStatus` effect, each on its own interval counted from the time the
+/// status was (re)applied.
+pub(crate) fn apply_status(
+    ctx: &ReducerContext,
+    world: &World,
+    effect_ctx: &EffectContext,
+    target: EntityId,
+    effect: &ApplyStatusEffect,
+    now: i64,
+) -> StatusInstance {
+    let def = StatusDef::find(ctx, effect.status_id as u64);
+    let stacking = def.as_ref().map(|def| def.stacking).unwrap_or_default();
+    let max_stacks = def
+        .as_ref()
+        .map(|def| def.max_stacks.max(1))
+        .unwrap_or(i32::MAX);
+    let stacks = effect.stacks.max(1).min(max_stacks);
+    let expires_at_ms = if effect.duration_ms > 0 {
+        now + effect.duration_ms as i64
+    } else {
+        i64::MAX
+    };
+    // Channelled spells fire their periodic effects while the channel lasts.
+    let periodic_effects = if effect_ctx.spell.channeled {
+        Vec::new()
+    } else {
+        effect.periodic_effects.clone()
+    };
+    let periodic_interval_ms = effect_ctx
+        .spell
+        .periodic_effects(Some(&periodic_effects))
+        .map(|(_, timing)| timing.interval_ms)
+        .min();
+    let next_tick_at_ms = effect_ctx
+        .spell
+        .next_periodic_tick(Some(&periodic_effects), 0)
+        .map(|next| now + next);
+
+    let existing = match stacking {
+        StackingPolicy::Independent => None,
+        StackingPolicy::Refresh | StackingPolicy::AddStacks => {
+            StatusInstance::find_for(ctx, world.id, target, effect.status_id)
+                .into_iter()
+                .next()
+        }
+    };
+
+    let status = match existing {
+        Some(mut status) => {
+            status.stacks = match stacking {
+                StackingPolicy::AddStacks => status.stacks.saturating_add(stacks).min(max_stacks),
+                _ => status.stacks.max(stacks),
+            };
+            status.source = effect_ctx.caster;
+            status.applied_at_ms = now;
+            status.expires_at_ms = expires_at_ms;
+            status.periodic_interval_ms = periodic_interval_ms;
+            status.next_tick_at_ms = next_tick_at_ms;
+            status.spell_id = Some(effect_ctx.spell.id);
+            status.periodic_effects = periodic_effects;
+            status.update(ctx)
+        }
+        None => StatusInstance {
+            id: 0,
+            world_id: world.id,
+            status_id: effect.status_id,
+            owner: target,
+            source: effect_ctx.caster,
+            applied_at_ms: now,
+            expires_at_ms,
+            stacks,
+            periodic_interval_ms,
+            next_tick_at_ms,
+            spell_id: Some(effect_ctx.spell.id),
+            periodic_effects,
+        }
+        .insert(ctx),
+    };
+
+    if world.debug_spells {
+        log::debug!(
+            "[World#{}] [Spells] Status#{} applied to Entity#{} by Entity#{}, stacks: {}",
+            world.id,
+            status.status_id,
+            target,
+            effect_ctx.caster,
+            status.stacks
+        );
+    }
+
+    status
+}
+
+/// Removes stacks of a status from the target. Instances left without stacks are
+/// deleted, a stack count of zero or less removes every instance of the status.
+/// Returns the number of stacks removed.
+pub(crate) fn remove_status(
+    ctx: &ReducerContext,
+    world: &World,
+    target: EntityId,
+    effect: &RemoveStatusEffect,
+) -> i32 {
+    let mut removed_stacks = 0;
+    let mut remaining = if effect.stacks > 0 {
+        effect.stacks
+    } else {
+        i32::MAX
+    };
+
+    for mut status in StatusInstance::find_for(ctx, world.id, target, effect.status_id) {
+        if remaining <= 0 {
+            break;
+        }
+
+        let removed = status.stacks.min(remaining);
+        remaining -= removed;
+        removed_stacks += removed;
+        status.stacks -= removed;
+        if status.stacks <= 0 {
+            status.delete(ctx);
+        } else {
+            status.update(ctx);
+        }
+    }
+
+    removed_stacks
+}
+
+/// Removes up to `max_statuses` statuses from the target matching the dispel's
+/// tags (any status if no tag is given). Hostile casters dispel buffs, friendly
+/// ones dispel debuffs. Statuses without a definition cannot be dispelled.
+/// Matching statuses are removed by descending priority, then oldest first.
+/// Returns the removed instances.
+pub(crate) fn dispel(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    caster: EntityId,
+    target: EntityId,
+    effect: &DispelEffect,
+) -> Vec<StatusInstance> {
+    let kind = if executor.is_hostile(ctx, world, caster, target) {
+        StatusKind::Buff
+    } else {
+        StatusKind::Debuff
+    };
+
+    let mut defs: HashMap<StatusId, Option<StatusDef>> = HashMap::new();
+    let mut candidates = StatusInstance::for_owner(ctx, world.id, target)
+        .into_iter()
+        .filter_map(|status| {
+            let def = defs
+                .entry(status.status_id)
+                .or_insert_with(|| StatusDef::find(ctx, status.status_id as u64))
+                .as_ref()?;
+            let matches_tags = effect.filter_tags.is_empty()
+                || def.tags.iter().any(|tag| effect.filter_tags.contains(tag));
+            (def.kind == kind && matches_tags).then_some((def.priority, status))
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by(|(priority_a, a), (priority_b, b)| {
+        priority_b
+            .cmp(priority_a)
+            .then(a.applied_at_ms.cmp(&b.applied_at_ms))
+            .then(a.id.cmp(&b.id))
+    });
+
+    let removed = candidates
+        .into_iter()
+        .take(effect.max_statuses as usize)
+        .map(|(_, status)| {
+            status.delete(ctx);
+            status
+        })
+        .collect::<Vec<_>>();
+
+    if world.debug_spells && !removed.is_empty() {
+        log::debug!(
+            "[World#{}] [Spells] Entity#{} dispelled {:?} from Entity#{}",
+            world.id,
+            caster,
+            removed
+                .iter()
+                .map(|status| status.status_id)
+                .collect::<Vec<_>>(),
+            target
+        );
+    }
+
+    removed
+}
+
+/// Fires the periodic effects of statuses that are due and expires statuses
+/// whose duration elapsed, firing their `OnExpire` effects.
+pub(crate) fn tick_statuses(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    now: i64,
+) {
+    let mut spells: HashMap<u64, Option<SpellDef>> = HashMap::new();
+
+    for status in StatusInstance::as_vec(ctx, world.id) {
+        // Effects fired earlier in this pass may have removed or changed this status.
+        let Some(mut status) = StatusInstance::find(ctx, status.id) else {
+            continue;
+        };
+        let spell = status.spell_id.and_then(|spell_id| {
+            spells
+                .entry(spell_id as u64)
+                .or_insert_with(|| SpellDef::find(ctx, spell_id as u64))
+                .clone()
+        });
+        let target = TargetInstance::Entity(status.owner);
+
+        if let Some(spell) = &spell
+            && status.next_tick_at_ms.is_some()
+        {
+            let effect_ctx = EffectContext {
+                caster: status.source,
+                spell,
+                target: &target,
+            };
+            let mut ticked = false;
+            while let Some(tick_at) = status.next_tick_at_ms
+                && tick_at <= now
+                && tick_at <= status.expires_at_ms
+            {
+                // A previous tick may have removed this status.
+                if ticked && StatusInstance::find(ctx, status.id).is_none() {
+                    break;
+                }
+
+                fire_periodic_effects(
+                    ctx,
+                    world,
+                    executor,
+                    &effect_ctx,
+                    &[status.owner],
+                    Some(&status.periodic_effects),
+                    tick_at - status.applied_at_ms,
+                );
+                status.next_tick_at_ms = spell
+                    .next_periodic_tick(
+                        Some(&status.periodic_effects),
+                        tick_at - status.applied_at_ms,
+                    )
+                    .map(|next| status.applied_at_ms + next);
+                ticked = true;
+            }
+            if ticked {
+                // Periodic effects may have removed or changed this status, a
+                // refreshed status keeps the schedule it was reapplied with.
+                let Some(mut current) = StatusInstance::find(ctx, status.id) else {
+                    continue;
+                };
+                if current.applied_at_ms == status.applied_at_ms {
+                    current.next_tick_at_ms = status.next_tick_at_ms;
+                    status = current.update(ctx);
+                } else {
+                    status = current;
+                }
+            }
+        }
+
+        if status.expires_at_ms > now {
+            continue;
+        }
+
+        if let Some(spell) = &spell {
+            let effect_ctx = EffectContext {
+                caster: status.source,
+                spell,
+                target: &target,
+            };
+            for effect in spell.effects_at(&EffectTiming::OnExpire) {
+                resolve_effect(ctx, world, executor, &effect_ctx, status.owner, effect);
+            }
+        }
+
+        if world.debug_spells {
+            log::debug!(
+                "[World#{}] [Spells] Status#{} expired on Entity#{}",
+                world.id,
+                status.status_id,
+                status.owner
+            );
+        }
+
+        status.delete(ctx);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use spacetimedb::ReducerContext;
+
+use crate::{
+    navigation::{NavigationAgent, NavigationAgentId},
+    spells::{
+        ActiveCast, Cooldown, SpellDef, SpellExecutor,
+        casting::{complete_cast, tick_channel},
+        combat_event::clear_combat_events,
+        interrupt::{clear_school_lockouts, interrupt_moving_casters},
+        projectile::tick_projectiles,
+        status::tick_statuses,
+    },
+    utils::{Entity, LogStopwatch, WorldEntity, now_ms},
+    world::World,
+};
+
+/// Advances the spells of a world: interrupts casters whose agent moved, moves
+/// projectiles, ticks channels, completes casts whose cast time elapsed, ticks
+/// and expires status effects and removes expired cooldowns, lockouts and
+/// combat events.
+pub fn tick_spells(
+    ctx: &ReducerContext,
+    world: &World,
+    executor: &mut impl SpellExecutor,
+    agents: &HashMap<NavigationAgentId, NavigationAgent>,
+) {
+    let mut sw = LogStopwatch::new(ctx, world, "spells_tick".to_string(), world.debug_spells);
+    let now = now_ms(ctx);
+
+    sw.span("interrupt_moving_casters");
+    interrupt_moving_casters(ctx, world, agents);
+
+    sw.span("tick_projectiles");
+    tick_projectiles(ctx, world, executor, now);
+
+    sw.span("complete_casts");
+    let mut collision_query = None;
+    for mut cast in ActiveCast::as_vec(ctx, world.id) {
+        match SpellDef::find(ctx, cast.spell_id as u64) {
+            Some(spell) => {
+                let ticked = cast.flags.is_channel
+                    && tick_channel(
+                        ctx,
+                        world,
+                        executor,
+                        &mut collision_query,
+                        &spell,
+                        &mut cast,
+                        now,
+                    );
+                if cast.will_end_at_ms <= now {
+                    complete_cast(ctx, world, executor, &mut collision_query, &spell, &cast);
+                } else if ticked {
+                    cast.update(ctx);
+                }
+            }
+            None => {
+                log::error!(
+                    "[World#{}] [Spells] Spell#{} of cast#{} not found, dropping cast",
+                    world.id,
+                    cast.spell_id,
+                    cast.id
+                );
+                cast.delete(ctx);
+            }
+        }
+    }
+
+    sw.span("tick_statuses");
+    tick_statuses(ctx, world, executor, now);
+
+    sw.span("clear_cooldowns");
+    for cooldown in Cooldown::as_vec(ctx, world.id) {
+        if cooldown.ready_at_ms <= now {
+            cooldown.delete(ctx);
+        }
+    }
+    clear_school_lockouts(ctx, world, now);
+
+    sw.span("clear_combat_events");
+    clear_combat_events(ctx, world, now);
+    sw.end();
+}
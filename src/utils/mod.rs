@@ -1,7 +1,9 @@
 mod delta_time;
 mod entity;
 mod log_stopwatch;
+mod time;
 
 pub use delta_time::*;
 pub use entity::*;
 pub use log_stopwatch::*;
+pub use time::*;
@@ -0,0 +1,6 @@
+use spacetimedb::ReducerContext;
+
+/// Returns the timestamp of the current reducer call in milliseconds since the Unix epoch.
+pub fn now_ms(ctx: &ReducerContext) -> i64 {
+    ctx.timestamp.to_micros_since_unix_epoch() / 1_000
+}
@@ -7,6 +7,7 @@ use spacetimedb::{ReducerContext, ScheduleAt, Table, table};
 use crate::{
     collisions,
     navigation::{self, NavigationAgent, NavigationAgentId},
+    spells::{self, SpellExecutor},
     utils::{Entity, get_delta_time},
 };
 
@@ -25,6 +26,10 @@ pub struct World {
     #[builder(default = 0.0)]
     pub aabb_dilation_factor: f32,
 
+    #[builder(default = 10_000)]
+    /// How long combat events are kept before being removed, in milliseconds.
+    pub combat_event_retention_ms: i64,
+
     #[builder(default = false)]
     /// If true, enables debug logging and print timings for various systems.
     pub debug: bool,
@@ -34,6 +39,8 @@ pub struct World {
     pub debug_behavior_trees: bool,
     #[builder(default = debug)]
     pub debug_collisions: bool,
+    #[builder(default = debug)]
+    pub debug_spells: bool,
     #[builder(default = 0.05)]
     /// The rate at which to sample debug information, between 0.0 and 1.0.
     pub debug_sample_rate: f32,
@@ -70,6 +77,7 @@ impl Entity for World {
 
     fn delete(&self, ctx: &ReducerContext) {
         ctx.db.steng_world().id().delete(self.id);
+        navigation::invalidate_archipelago(self.id);
     }
 
     fn clear(ctx: &ReducerContext) {
@@ -88,6 +96,7 @@ pub fn tick_world(
     world_id: WorldId,
     scheduled_at: ScheduleAt,
     characters: impl Iterator<Item = navigation::Character>,
+    spell_executor: &mut impl SpellExecutor,
 ) -> HashMap<NavigationAgentId, NavigationAgent> {
     let delta_time = get_delta_time(scheduled_at);
 
@@ -95,6 +104,7 @@ pub fn tick_world(
 
     let agents = navigation::tick_navigation(ctx, &world, delta_time, characters);
     collisions::tick_collisions(ctx, &world);
+    spells::tick_spells(ctx, &world, spell_executor, &agents);
 
     agents
 }